@@ -0,0 +1,91 @@
+//! Page activity: while the browser tab is hidden the simulation and the outbound state are paused,
+//! the fixed timestep never runs more than a few catch-up steps after a long frame gap, and the
+//! updates queued by js in the meantime are applied from the coalescing inbox (see `inbox`).
+use bevy::ecs::schedule::ShouldRun;
+use bevy::input::InputSystem;
+use bevy::prelude::*;
+use bevy::window::WindowFocused;
+
+use crate::controls::ControlsLabel;
+use crate::resources::{InputActions, PageActivity};
+use crate::wasm::GAME_PAUSED;
+use crate::{MAX_CATCH_UP_STEPS, TIME_STEP};
+
+pub struct PageActivityPlugin;
+
+impl Plugin for PageActivityPlugin {
+    fn build(
+        &self,
+        app: &mut App,
+    ) {
+        app.init_resource::<PageActivity>();
+        app.add_system_to_stage(
+            CoreStage::PreUpdate,
+            page_activity_system
+                .after(InputSystem)
+                .before(ControlsLabel::Actions),
+        );
+    }
+}
+
+/// Fixed timestep run criteria, like `FixedTimestep` but dropping the time above
+/// `MAX_CATCH_UP_STEPS` steps (e.g. after the tab was hidden) instead of running it all at once
+pub fn capped_fixed_step(
+    mut accumulator: Local<f64>,
+    mut last_update: Local<Option<f64>>,
+    time: Res<Time>,
+) -> ShouldRun {
+    // the run criteria is re-evaluated after every step: only accumulate once per frame
+    let now = time.seconds_since_startup();
+    if *last_update != Some(now) {
+        *last_update = Some(now);
+        *accumulator = (*accumulator + time.delta_seconds_f64())
+            .min(MAX_CATCH_UP_STEPS as f64 * TIME_STEP as f64);
+    }
+
+    if *accumulator >= TIME_STEP as f64 {
+        *accumulator -= TIME_STEP as f64;
+        ShouldRun::YesAndCheckAgain
+    } else {
+        ShouldRun::No
+    }
+}
+
+/// Tracks the page visibility and the window focus. Losing the focus releases the held keys (their
+/// release events never arrive).
+fn page_activity_system(
+    mut activity: ResMut<PageActivity>,
+    mut focus_events: EventReader<WindowFocused>,
+    mut keyboard_input: ResMut<Input<KeyCode>>,
+    mut input_actions: ResMut<InputActions>,
+) {
+    for event in focus_events.iter() {
+        activity.focused = event.focused;
+        if !event.focused {
+            let pressed_keys = keyboard_input.get_pressed().copied().collect::<Vec<_>>();
+            for key in pressed_keys {
+                keyboard_input.reset(key);
+            }
+        }
+    }
+
+    #[cfg(target_arch = "wasm32")]
+    let hidden = web_sys::window()
+        .and_then(|window| window.document())
+        .map(|document| document.hidden())
+        .unwrap_or(false);
+    #[cfg(not(target_arch = "wasm32"))]
+    let hidden = false;
+
+    if hidden != activity.hidden {
+        activity.hidden = hidden;
+        if hidden {
+            info!("Page hidden, pausing the game");
+        } else {
+            info!("Page visible again, resynchronising");
+            // drop the shot or weapon switch latched before hiding
+            *input_actions = InputActions::default();
+        }
+        GAME_PAUSED.with(|paused| *paused.borrow_mut() = hidden);
+    }
+}
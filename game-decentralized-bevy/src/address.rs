@@ -0,0 +1,168 @@
+//! Massa addresses: a prefix ("AU" for the user accounts, "AS" for the smart contracts) followed
+//! by the base58check encoding (double sha256 checksum) of a varint version and a 32 bytes hash.
+//! The events are decoded into a [`MassaAddress`], so an invalid address fails the whole event.
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::str::FromStr;
+
+use crate::errors::AddressError;
+
+const USER_ADDRESS_PREFIX: &str = "AU";
+const CONTRACT_ADDRESS_PREFIX: &str = "AS";
+// size of the hash following the version in the decoded address
+const ADDRESS_HASH_SIZE: usize = 32;
+// characters kept at each end of the short address (e.g. `AU12…f9k3`)
+const SHORT_ADDRESS_EDGE: usize = 4;
+
+/// A validated Massa address, kept in its textual form
+#[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub struct MassaAddress(String);
+
+impl MassaAddress {
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+
+    /// The address of a smart contract, rather than of a user account
+    pub fn is_contract(&self) -> bool {
+        self.0.starts_with(CONTRACT_ADDRESS_PREFIX)
+    }
+
+    /// Short form for the UI, keeping both ends of the address (e.g. `AU12…f9k3`)
+    pub fn short(&self) -> String {
+        let chars = self.0.chars().collect::<Vec<char>>();
+        if chars.len() <= 2 * SHORT_ADDRESS_EDGE + 1 {
+            return self.0.clone();
+        }
+        let start = chars[..SHORT_ADDRESS_EDGE].iter().collect::<String>();
+        let end = chars[chars.len() - SHORT_ADDRESS_EDGE..]
+            .iter()
+            .collect::<String>();
+        format!("{}…{}", start, end)
+    }
+}
+
+impl FromStr for MassaAddress {
+    type Err = AddressError;
+
+    fn from_str(address: &str) -> Result<Self, Self::Err> {
+        let encoded = address
+            .strip_prefix(USER_ADDRESS_PREFIX)
+            .or_else(|| address.strip_prefix(CONTRACT_ADDRESS_PREFIX))
+            .ok_or_else(|| AddressError::InvalidPrefix(address.to_string()))?;
+        let payload = bs58::decode(encoded)
+            .with_check(None)
+            .into_vec()
+            .map_err(|_| AddressError::InvalidEncoding(address.to_string()))?;
+
+        // the version is a varint: its last byte has the high bit cleared
+        let version_size = payload
+            .iter()
+            .position(|byte| byte & 0x80 == 0)
+            .map(|index| index + 1)
+            .ok_or_else(|| AddressError::InvalidSize(address.to_string()))?;
+        if payload.len() != version_size + ADDRESS_HASH_SIZE {
+            return Err(AddressError::InvalidSize(address.to_string()));
+        }
+        Ok(MassaAddress(address.to_string()))
+    }
+}
+
+impl TryFrom<String> for MassaAddress {
+    type Error = AddressError;
+
+    fn try_from(address: String) -> Result<Self, Self::Error> {
+        MassaAddress::from_str(&address)
+    }
+}
+
+impl From<MassaAddress> for String {
+    fn from(address: MassaAddress) -> Self {
+        address.0
+    }
+}
+
+impl fmt::Display for MassaAddress {
+    fn fmt(
+        &self,
+        f: &mut fmt::Formatter<'_>,
+    ) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // version 0 followed by the bytes 1 to 32 (user) or 33 to 64 (contract)
+    const USER_ADDRESS: &str = "AU1SkB92YpWm4Q2ijQHH34cqbKkCZWszsiQgHVjtNeFF2Ds6STz";
+    const CONTRACT_ADDRESS: &str = "AS1FbM83suok6gBMHACMLfoSRCsjgJgnJcyJ9E3JsPjhSwhk3AKS";
+
+    #[test]
+    fn valid_addresses_are_parsed() {
+        let user = USER_ADDRESS.parse::<MassaAddress>().unwrap();
+        assert_eq!(user.as_str(), USER_ADDRESS);
+        assert!(!user.is_contract());
+
+        let contract = CONTRACT_ADDRESS.parse::<MassaAddress>().unwrap();
+        assert_eq!(contract.to_string(), CONTRACT_ADDRESS);
+        assert!(contract.is_contract());
+    }
+
+    #[test]
+    fn an_unknown_prefix_is_rejected() {
+        let address = USER_ADDRESS.replacen("AU", "AX", 1);
+        assert_eq!(
+            address.parse::<MassaAddress>(),
+            Err(AddressError::InvalidPrefix(address.clone()))
+        );
+        assert!(matches!(
+            "".parse::<MassaAddress>(),
+            Err(AddressError::InvalidPrefix(_))
+        ));
+    }
+
+    #[test]
+    fn a_corrupted_checksum_is_rejected() {
+        let address = format!("{}y", &USER_ADDRESS[..USER_ADDRESS.len() - 1]);
+        assert_eq!(
+            address.parse::<MassaAddress>(),
+            Err(AddressError::InvalidEncoding(address.clone()))
+        );
+        // not base58 at all
+        assert!(matches!(
+            "AU0OIl".parse::<MassaAddress>(),
+            Err(AddressError::InvalidEncoding(_))
+        ));
+    }
+
+    #[test]
+    fn a_wrong_payload_size_is_rejected() {
+        // valid checksums over a 31 and a 33 bytes hash
+        for address in [
+            "AU16qJFWMMHFy3xDdLmvUeyc2S6FrWRhJP51HsvDYdz9d1FsYG",
+            "AU12xdZkRqSiMTzebC9kqn6yBUVjg2Wt6SqmWPorHkXPrtKjRVaZt",
+        ] {
+            assert_eq!(
+                address.parse::<MassaAddress>(),
+                Err(AddressError::InvalidSize(address.to_string()))
+            );
+        }
+    }
+
+    #[test]
+    fn addresses_are_decoded_from_json_strings() {
+        let json = format!("\"{}\"", USER_ADDRESS);
+        let address = serde_json::from_str::<MassaAddress>(&json).unwrap();
+        assert_eq!(serde_json::to_string(&address).unwrap(), json);
+        assert!(serde_json::from_str::<MassaAddress>("\"AU1\"").is_err());
+    }
+
+    #[test]
+    fn the_short_form_keeps_both_ends() {
+        let address = USER_ADDRESS.parse::<MassaAddress>().unwrap();
+        assert_eq!(address.short(), "AU1S…6STz");
+    }
+}
@@ -0,0 +1,247 @@
+//! Gameplay sounds: effects are requested through [`PlaySound`] events, panned by their position
+//! on the screen (relative to the camera, which moves while spectating) and attenuated by their
+//! distance to the local player. Effects and music play on their own channels, and nothing plays
+//! before the browser unlocks the audio (on the first user input).
+use bevy::prelude::*;
+use bevy::utils::HashMap;
+use bevy_kira_audio::{AudioApp, AudioChannel, AudioPlugin, AudioSource};
+
+use crate::components::LocalPlayer;
+use crate::resources::{AudioSettings, WinSize};
+use crate::wasm::{AUDIO_UNLOCK_REQUESTED, AUDIO_VOLUME_REQUESTS};
+
+const MUSIC_TRACK: &str = "sounds/music.wav";
+// distance to the local player at which a positional sound plays at half volume
+const SOUND_FALLOFF_DISTANCE: f32 = 400.0;
+
+// audio channels
+pub struct SfxChannel;
+pub struct MusicChannel;
+
+#[derive(SystemLabel, Debug, Clone, PartialEq, Eq, Hash)]
+pub enum AudioLabel {
+    Settings,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum SoundEffect {
+    LaserFire,
+    Hit,
+    Explosion,
+    TokenPickup,
+    PlayerJoined,
+    PlayerLeft,
+}
+
+impl SoundEffect {
+    pub const ALL: [SoundEffect; 6] = [
+        SoundEffect::LaserFire,
+        SoundEffect::Hit,
+        SoundEffect::Explosion,
+        SoundEffect::TokenPickup,
+        SoundEffect::PlayerJoined,
+        SoundEffect::PlayerLeft,
+    ];
+
+    pub fn path(&self) -> &'static str {
+        match self {
+            SoundEffect::LaserFire => "sounds/laser.wav",
+            SoundEffect::Hit => "sounds/hit.wav",
+            SoundEffect::Explosion => "sounds/explosion.wav",
+            SoundEffect::TokenPickup => "sounds/token_pickup.wav",
+            SoundEffect::PlayerJoined => "sounds/player_joined.wav",
+            SoundEffect::PlayerLeft => "sounds/player_left.wav",
+        }
+    }
+
+    /// base volume, before the channel volume and the distance attenuation
+    pub fn volume(&self) -> f32 {
+        match self {
+            SoundEffect::LaserFire => 0.4,
+            SoundEffect::Hit => 0.7,
+            SoundEffect::Explosion => 0.8,
+            SoundEffect::TokenPickup => 0.6,
+            SoundEffect::PlayerJoined | SoundEffect::PlayerLeft => 0.5,
+        }
+    }
+}
+
+/// A request to play a sound effect, either at a game position or centered (stereo)
+#[derive(Clone, Debug)]
+pub struct PlaySound {
+    pub effect: SoundEffect,
+    pub position: Option<Vec3>,
+}
+
+impl PlaySound {
+    pub fn at(
+        effect: SoundEffect,
+        position: Vec3,
+    ) -> Self {
+        Self {
+            effect,
+            position: Some(position),
+        }
+    }
+
+    pub fn stereo(effect: SoundEffect) -> Self {
+        Self {
+            effect,
+            position: None,
+        }
+    }
+}
+
+struct GameSounds {
+    effects: HashMap<SoundEffect, Handle<AudioSource>>,
+    music: Handle<AudioSource>,
+}
+
+pub struct GameAudioPlugin;
+
+impl Plugin for GameAudioPlugin {
+    fn build(
+        &self,
+        app: &mut App,
+    ) {
+        app.add_plugin(AudioPlugin);
+        app.add_audio_channel::<SfxChannel>();
+        app.add_audio_channel::<MusicChannel>();
+        app.init_resource::<AudioSettings>();
+        app.add_event::<PlaySound>();
+        app.add_startup_system(load_sounds_system);
+        app.add_system(audio_settings_system.label(AudioLabel::Settings));
+        app.add_system(play_sounds_system.after(AudioLabel::Settings));
+    }
+}
+
+fn load_sounds_system(
+    mut commands: Commands,
+    asset_server: Res<AssetServer>,
+) {
+    let effects = SoundEffect::ALL
+        .iter()
+        .map(|effect| (*effect, asset_server.load(effect.path())))
+        .collect::<HashMap<SoundEffect, Handle<AudioSource>>>();
+    commands.insert_resource(GameSounds {
+        effects,
+        music: asset_server.load(MUSIC_TRACK),
+    });
+}
+
+/// Applies the volume changes from js, unlocks the audio on the first user input and starts the
+/// background music once unlocked
+fn audio_settings_system(
+    mut settings: ResMut<AudioSettings>,
+    mut music_started: Local<bool>,
+    keyboard_input: Res<Input<KeyCode>>,
+    mouse_input: Res<Input<MouseButton>>,
+    touches: Res<Touches>,
+    game_sounds: Res<GameSounds>,
+    sfx_channel: Res<AudioChannel<SfxChannel>>,
+    music_channel: Res<AudioChannel<MusicChannel>>,
+) {
+    let volume_requests = AUDIO_VOLUME_REQUESTS.with(|requests| requests.take());
+    for (channel, volume) in volume_requests.iter() {
+        if !settings.set_volume(channel, *volume) {
+            warn!("Invalid volume request {:?} -> {:?}", channel, volume);
+        }
+    }
+
+    // browsers only let audio start after a user gesture
+    if !settings.unlocked {
+        let user_gesture = keyboard_input.get_just_pressed().next().is_some()
+            || mouse_input.get_just_pressed().next().is_some()
+            || touches.iter_just_pressed().next().is_some()
+            || AUDIO_UNLOCK_REQUESTED.with(|requested| requested.replace(false));
+        if user_gesture {
+            settings.unlocked = true;
+        }
+    }
+
+    if settings.is_changed() {
+        sfx_channel.set_volume((settings.master * settings.sfx) as f64);
+        music_channel.set_volume((settings.master * settings.music) as f64);
+    }
+
+    if settings.unlocked && !*music_started {
+        *music_started = true;
+        music_channel.play(game_sounds.music.clone()).looped();
+    }
+}
+
+/// Pans a sound at `x` from the left (0) to the right (1) edge of a viewport `width` wide,
+/// centered on `camera_x`
+fn stereo_panning(
+    x: f32,
+    camera_x: f32,
+    width: f32,
+) -> f32 {
+    if width <= 0.0 {
+        return 0.5;
+    }
+    (0.5 + 0.5 * (x - camera_x) / (width / 2.0)).clamp(0.0, 1.0)
+}
+
+fn play_sounds_system(
+    settings: Res<AudioSettings>,
+    game_sounds: Res<GameSounds>,
+    sfx_channel: Res<AudioChannel<SfxChannel>>,
+    win_size: Res<WinSize>,
+    mut play_sound_events: EventReader<PlaySound>,
+    listener_query: Query<&Transform, With<LocalPlayer>>,
+    camera_query: Query<&Transform, With<Camera2d>>,
+) {
+    // the local player is the listener, when spawned
+    let listener = listener_query
+        .get_single()
+        .map(|transform| transform.translation)
+        .ok();
+    let camera_x = camera_query
+        .get_single()
+        .map(|transform| transform.translation.x)
+        .unwrap_or_default();
+
+    for event in play_sound_events.iter() {
+        // sounds requested while the audio is locked are dropped, not queued
+        if !settings.unlocked {
+            continue;
+        }
+        let sound = match game_sounds.effects.get(&event.effect) {
+            Some(sound) => sound.clone(),
+            None => continue,
+        };
+
+        let mut volume = event.effect.volume();
+        let mut panning = 0.5;
+        if let Some(position) = event.position {
+            panning = stereo_panning(position.x, camera_x, win_size.w);
+            if let Some(listener) = listener {
+                let distance = position.truncate().distance(listener.truncate());
+                volume /= 1.0 + distance / SOUND_FALLOFF_DISTANCE;
+            }
+        }
+
+        sfx_channel
+            .play(sound)
+            .with_volume(volume as f64)
+            .with_panning(panning as f64);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sounds_are_panned_relative_to_the_camera() {
+        assert_eq!(stereo_panning(0.0, 0.0, 1000.0), 0.5);
+        assert_eq!(stereo_panning(-500.0, 0.0, 1000.0), 0.0);
+        assert_eq!(stereo_panning(250.0, 0.0, 1000.0), 0.75);
+        // a camera following a player on the right: the sounds at the world center are on the left
+        assert_eq!(stereo_panning(0.0, 250.0, 1000.0), 0.25);
+        assert_eq!(stereo_panning(250.0, 250.0, 1000.0), 0.5);
+        // clamped past the edges
+        assert_eq!(stereo_panning(2000.0, 250.0, 1000.0), 1.0);
+    }
+}
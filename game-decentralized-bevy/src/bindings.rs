@@ -0,0 +1,247 @@
+//! Key names, on-screen instructions and persistence (browser localStorage on wasm, a json file on
+//! native) for the [`InputBindings`].
+use bevy::prelude::*;
+use std::collections::BTreeMap;
+
+use crate::resources::{InputAction, InputBindings};
+
+// localStorage key (wasm) or file path (native) of the persisted bindings
+#[cfg(target_arch = "wasm32")]
+const INPUT_BINDINGS_STORAGE_KEY: &str = "starship_input_bindings";
+#[cfg(not(target_arch = "wasm32"))]
+const INPUT_BINDINGS_FILE: &str = "starship_input_bindings.json";
+
+// keys that can be bound to an action
+const BINDABLE_KEYS: [KeyCode; 50] = [
+    KeyCode::A,
+    KeyCode::B,
+    KeyCode::C,
+    KeyCode::D,
+    KeyCode::E,
+    KeyCode::F,
+    KeyCode::G,
+    KeyCode::H,
+    KeyCode::I,
+    KeyCode::J,
+    KeyCode::K,
+    KeyCode::L,
+    KeyCode::M,
+    KeyCode::N,
+    KeyCode::O,
+    KeyCode::P,
+    KeyCode::Q,
+    KeyCode::R,
+    KeyCode::S,
+    KeyCode::T,
+    KeyCode::U,
+    KeyCode::V,
+    KeyCode::W,
+    KeyCode::X,
+    KeyCode::Y,
+    KeyCode::Z,
+    KeyCode::Key0,
+    KeyCode::Key1,
+    KeyCode::Key2,
+    KeyCode::Key3,
+    KeyCode::Key4,
+    KeyCode::Key5,
+    KeyCode::Key6,
+    KeyCode::Key7,
+    KeyCode::Key8,
+    KeyCode::Key9,
+    KeyCode::Left,
+    KeyCode::Right,
+    KeyCode::Up,
+    KeyCode::Down,
+    KeyCode::Space,
+    KeyCode::Return,
+    KeyCode::Tab,
+    KeyCode::Escape,
+    KeyCode::Back,
+    KeyCode::LShift,
+    KeyCode::RShift,
+    KeyCode::LControl,
+    KeyCode::RControl,
+    KeyCode::F3,
+];
+
+/// Stable name of a key, as persisted and exchanged with js (e.g. `"Space"`, `"A"`)
+pub fn key_code_name(key: KeyCode) -> String {
+    format!("{:?}", key)
+}
+
+pub fn key_code_from_name(name: &str) -> Option<KeyCode> {
+    BINDABLE_KEYS
+        .iter()
+        .find(|key| key_code_name(**key).eq(name))
+        .copied()
+}
+
+/// Short label of a key for the on-screen texts
+pub fn key_code_label(key: KeyCode) -> String {
+    match key {
+        KeyCode::Left => "←".to_string(),
+        KeyCode::Right => "→".to_string(),
+        KeyCode::Up => "↑".to_string(),
+        KeyCode::Down => "↓".to_string(),
+        KeyCode::Key0 => "0".to_string(),
+        KeyCode::Key1 => "1".to_string(),
+        KeyCode::Key2 => "2".to_string(),
+        KeyCode::Key3 => "3".to_string(),
+        KeyCode::Key4 => "4".to_string(),
+        KeyCode::Key5 => "5".to_string(),
+        KeyCode::Key6 => "6".to_string(),
+        KeyCode::Key7 => "7".to_string(),
+        KeyCode::Key8 => "8".to_string(),
+        KeyCode::Key9 => "9".to_string(),
+        _ => key_code_name(key).to_uppercase(),
+    }
+}
+
+/// The on-screen instructions for the current bindings (e.g. "← → left/right, SPACE - shoot")
+pub fn instructions_text(bindings: &InputBindings) -> String {
+    let label = |action: InputAction| {
+        bindings
+            .key(action)
+            .map(key_code_label)
+            .unwrap_or_else(|| "?".to_string())
+    };
+    format!(
+        "{} {} left/right, {} {} thrust/brake, {} - shoot, {} - weapon, {} - boost, {} - scores",
+        label(InputAction::RotateLeft),
+        label(InputAction::RotateRight),
+        label(InputAction::Thrust),
+        label(InputAction::Brake),
+        label(InputAction::Fire),
+        label(InputAction::SwitchWeapon),
+        label(InputAction::Boost),
+        label(InputAction::ToggleScoreboard),
+    )
+}
+
+/// Serializes the bindings as a json object of action name to key name
+pub fn serialize_bindings(bindings: &InputBindings) -> String {
+    let named_bindings = bindings
+        .0
+        .iter()
+        .map(|(action, key)| (*action, key_code_name(*key)))
+        .collect::<BTreeMap<InputAction, String>>();
+    serde_json::to_string(&named_bindings).unwrap_or_default()
+}
+
+/// Reads persisted bindings on top of the defaults, skipping unknown actions or keys
+pub fn deserialize_bindings(data: &str) -> InputBindings {
+    let mut bindings = InputBindings::default();
+    let named_bindings = serde_json::from_str::<BTreeMap<String, String>>(data).unwrap_or_default();
+    for (action, key) in named_bindings.iter() {
+        if let (Some(action), Some(key)) = (InputAction::from_name(action), key_code_from_name(key))
+        {
+            bindings.rebind(action, key);
+        }
+    }
+    bindings
+}
+
+#[cfg(target_arch = "wasm32")]
+fn local_storage() -> Option<web_sys::Storage> {
+    web_sys::window().and_then(|window| window.local_storage().ok().flatten())
+}
+
+pub fn load_bindings() -> InputBindings {
+    #[cfg(target_arch = "wasm32")]
+    let persisted = local_storage()
+        .and_then(|storage| storage.get_item(INPUT_BINDINGS_STORAGE_KEY).ok().flatten());
+    #[cfg(not(target_arch = "wasm32"))]
+    let persisted = std::fs::read_to_string(INPUT_BINDINGS_FILE).ok();
+
+    persisted
+        .map(|data| deserialize_bindings(&data))
+        .unwrap_or_default()
+}
+
+pub fn save_bindings(bindings: &InputBindings) {
+    let data = serialize_bindings(bindings);
+
+    #[cfg(target_arch = "wasm32")]
+    let saved = local_storage()
+        .map(|storage| storage.set_item(INPUT_BINDINGS_STORAGE_KEY, &data).is_ok())
+        .unwrap_or(false);
+    #[cfg(not(target_arch = "wasm32"))]
+    let saved = std::fs::write(INPUT_BINDINGS_FILE, data).is_ok();
+
+    if !saved {
+        warn!("Could not persist the input bindings");
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn rebinding_to_a_free_key_swaps_nothing() {
+        let mut bindings = InputBindings::default();
+        assert_eq!(bindings.rebind(InputAction::Fire, KeyCode::F), None);
+        assert_eq!(bindings.key(InputAction::Fire), Some(KeyCode::F));
+        assert_eq!(bindings.key(InputAction::SwitchWeapon), Some(KeyCode::Q));
+    }
+
+    #[test]
+    fn rebinding_to_a_used_key_swaps_the_bindings() {
+        let mut bindings = InputBindings::default();
+        assert_eq!(
+            bindings.rebind(InputAction::Fire, KeyCode::Q),
+            Some(InputAction::SwitchWeapon)
+        );
+        assert_eq!(bindings.key(InputAction::Fire), Some(KeyCode::Q));
+        assert_eq!(
+            bindings.key(InputAction::SwitchWeapon),
+            Some(KeyCode::Space)
+        );
+    }
+
+    #[test]
+    fn rebinding_an_action_to_its_own_key_changes_nothing() {
+        let mut bindings = InputBindings::default();
+        assert_eq!(bindings.rebind(InputAction::Fire, KeyCode::Space), None);
+        assert_eq!(bindings.0, InputBindings::default().0);
+    }
+
+    #[test]
+    fn rebinding_an_unbound_action_to_a_used_key_unbinds_the_other() {
+        let mut bindings = InputBindings::default();
+        bindings.0.remove(&InputAction::Boost);
+        assert_eq!(
+            bindings.rebind(InputAction::Boost, KeyCode::Space),
+            Some(InputAction::Fire)
+        );
+        assert_eq!(bindings.key(InputAction::Boost), Some(KeyCode::Space));
+        assert_eq!(bindings.key(InputAction::Fire), None);
+    }
+
+    #[test]
+    fn the_persisted_bindings_round_trip() {
+        let mut bindings = InputBindings::default();
+        // a cycle of three actions, applied in any order on top of the defaults
+        bindings.0.insert(InputAction::RotateLeft, KeyCode::Right);
+        bindings.0.insert(InputAction::RotateRight, KeyCode::Up);
+        bindings.0.insert(InputAction::Thrust, KeyCode::Left);
+        bindings.0.insert(InputAction::Fire, KeyCode::F);
+
+        let data = serialize_bindings(&bindings);
+        assert_eq!(deserialize_bindings(&data).0, bindings.0);
+    }
+
+    #[test]
+    fn the_unknown_persisted_actions_and_keys_keep_the_defaults() {
+        let data = r#"{"Fire":"NotAKey","Dance":"D","Boost":"B"}"#;
+        let bindings = deserialize_bindings(data);
+        assert_eq!(bindings.key(InputAction::Fire), Some(KeyCode::Space));
+        assert_eq!(bindings.key(InputAction::Boost), Some(KeyCode::B));
+
+        assert_eq!(
+            deserialize_bindings("not json").0,
+            InputBindings::default().0
+        );
+    }
+}
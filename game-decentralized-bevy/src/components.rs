@@ -1,7 +1,12 @@
 use bevy::math::Vec2;
-use bevy::prelude::{Component, Entity, Quat, Vec3};
+use bevy::prelude::{Component, Quat, Vec3};
 use bevy::reflect::Uuid;
 use bevy::time::Timer;
+use std::str::FromStr;
+
+use crate::events::{LaserDespawnReason, PlayerLaserSerializedData};
+use crate::weapons::WeaponKind;
+use crate::LASER_MAX_LIFETIME;
 
 #[derive(Component, Clone, Debug)]
 pub struct SpriteSize(pub Vec2);
@@ -17,10 +22,60 @@ pub struct Movable {
     pub auto_despawn: bool,
 }
 
-#[derive(Component, Clone, Debug)]
+impl Movable {
+    /// Why the laser has to be despawned, if it does and it is despawned automatically
+    pub fn laser_despawn_reason(
+        &self,
+        laser: &LaserData,
+        position: Vec3,
+        bounds: Vec2,
+        now: f64,
+    ) -> Option<LaserDespawnReason> {
+        if !self.auto_despawn {
+            return None;
+        }
+        laser.despawn_reason(position, bounds, now)
+    }
+}
+
+#[derive(Component, Clone, Debug, Default)]
 pub struct Velocity {
+    /// max linear speed in units per second (the constant speed of lasers)
     pub linear: f32,
+    /// max rotation speed in radians per second
     pub rotational: f32,
+    /// current linear velocity in units per second
+    pub vector: Vec2,
+    /// current angular velocity in radians per second
+    pub angular: f32,
+}
+
+#[derive(Component, Clone, Debug)]
+pub struct Health {
+    pub current: f32,
+    pub max: f32,
+}
+
+impl Health {
+    pub fn new(max: f32) -> Self {
+        Self { current: max, max }
+    }
+
+    pub fn heal(
+        &mut self,
+        amount: f32,
+    ) {
+        self.current = (self.current + amount).min(self.max);
+    }
+
+    /// Applies `amount` damage, returns true when the health is depleted
+    pub fn damage(
+        &mut self,
+        amount: f32,
+    ) -> bool {
+        self.current = (self.current - amount).max(0.0);
+        self.current <= 0.0
+    }
 }
 
 #[derive(Component, Clone, Debug)]
@@ -47,8 +102,42 @@ impl Default for ExplosionTimer {
     }
 }
 
+/// The name tag of a player, a child of its ship
 #[derive(Component)]
-pub struct AnimateNameTranslation(pub Entity);
+pub struct PlayerTag;
+
+/// A bar of a player name tag
+#[derive(Component, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum TagBar {
+    Background,
+    Health,
+    Shield,
+}
+
+#[derive(Component)]
+pub struct InstructionsText;
+
+/// Root of the screen shown for the current game state
+#[derive(Component)]
+pub struct GameStateScreen;
+
+#[derive(Component)]
+pub struct GameStateScreenText;
+
+#[derive(Component)]
+pub struct ScoreboardText;
+
+// on-screen touch controls
+#[derive(Component)]
+pub struct TouchControlsRoot;
+
+#[derive(Component, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum TouchButton {
+    RotateLeft,
+    RotateRight,
+    Thrust,
+    Fire,
+}
 
 //components that dont exchange data with react (just for internal markers use)
 #[derive(Component)]
@@ -62,4 +151,192 @@ pub struct LaserData {
     pub player_uuid: String,
     pub start_pos: Vec3,
     pub start_rot: Quat,
+    pub weapon: WeaponKind,
+    /// game time (seconds since startup) of the shot
+    pub spawned_at: f64,
+}
+
+impl LaserData {
+    /// A laser received from the chain, shot `state.age` seconds before the game time `now`. None
+    /// for a laser without a valid uuid.
+    pub fn from_remote(
+        state: &PlayerLaserSerializedData,
+        now: f64,
+    ) -> Option<Self> {
+        Some(Self {
+            uuid: Uuid::from_str(&state.uuid).ok()?,
+            player_uuid: state.player_uuid.clone(),
+            start_pos: Vec3::new(
+                state.start_x.unwrap_or(state.x) as f32,
+                state.start_y.unwrap_or(state.y) as f32,
+                1.0,
+            ),
+            start_rot: Quat::from_array([0., 0., state.rot as f32, state.w as f32]),
+            weapon: state.weapon,
+            spawned_at: now - state.age,
+        })
+    }
+
+    /// Why the laser has to be despawned at `position` and game time `now`, if it does. The limits
+    /// only depend on the shot, so every client despawns a given laser at the same point.
+    pub fn despawn_reason(
+        &self,
+        position: Vec3,
+        bounds: Vec2,
+        now: f64,
+    ) -> Option<LaserDespawnReason> {
+        if position.truncate().distance(self.start_pos.truncate()) > self.weapon.projectile_range()
+        {
+            Some(LaserDespawnReason::Range)
+        } else if now - self.spawned_at > LASER_MAX_LIFETIME {
+            Some(LaserDespawnReason::Lifetime)
+        } else if position.x.abs() > bounds.x / 2. || position.y.abs() > bounds.y / 2. {
+            Some(LaserDespawnReason::OutOfBounds)
+        } else {
+            None
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::LASER_MAX_RANGE;
+
+    const BOUNDS: Vec2 = Vec2::from_array([2000.0, 2000.0]);
+    const LASER_UUID: &str = "67e55044-10b1-426f-9247-bb680e5fe0c8";
+
+    fn laser(weapon: WeaponKind) -> LaserData {
+        LaserData {
+            uuid: Uuid::from_str(LASER_UUID).unwrap(),
+            player_uuid: "player".to_string(),
+            start_pos: Vec3::new(0.0, 0.0, 1.0),
+            start_rot: Quat::IDENTITY,
+            weapon,
+            spawned_at: 10.0,
+        }
+    }
+
+    fn remote_state(
+        x: f64,
+        start_x: Option<f64>,
+        age: f64,
+    ) -> PlayerLaserSerializedData {
+        PlayerLaserSerializedData {
+            player_uuid: "player".to_string(),
+            uuid: LASER_UUID.to_string(),
+            x,
+            y: 0.0,
+            rot: 0.0,
+            w: 1.0,
+            weapon: WeaponKind::SingleLaser,
+            start_x,
+            start_y: start_x.map(|_| 0.0),
+            age,
+        }
+    }
+
+    #[test]
+    fn a_laser_within_its_limits_is_kept() {
+        let laser = laser(WeaponKind::SingleLaser);
+        let position = Vec3::new(LASER_MAX_RANGE, 0.0, 1.0);
+        assert_eq!(
+            laser.despawn_reason(position, BOUNDS, 10.0 + LASER_MAX_LIFETIME),
+            None
+        );
+    }
+
+    #[test]
+    fn a_laser_past_the_range_of_its_weapon_is_despawned() {
+        let position = Vec3::new(0.0, -300.0, 1.0);
+        assert_eq!(
+            laser(WeaponKind::SpreadShot).despawn_reason(position, BOUNDS, 10.0),
+            Some(LaserDespawnReason::Range)
+        );
+        assert_eq!(
+            laser(WeaponKind::SingleLaser).despawn_reason(position, BOUNDS, 10.0),
+            None
+        );
+        // the range is checked before the bounds
+        let far = Vec3::new(BOUNDS.x, 0.0, 1.0);
+        assert_eq!(
+            laser(WeaponKind::ChargedBeam).despawn_reason(far, BOUNDS, 10.0),
+            Some(LaserDespawnReason::Range)
+        );
+    }
+
+    #[test]
+    fn a_laser_past_its_lifetime_is_despawned() {
+        let laser = laser(WeaponKind::SingleLaser);
+        let now = 10.0 + LASER_MAX_LIFETIME + 0.1;
+        assert_eq!(
+            laser.despawn_reason(Vec3::ZERO, BOUNDS, now),
+            Some(LaserDespawnReason::Lifetime)
+        );
+    }
+
+    #[test]
+    fn a_laser_out_of_the_screen_is_despawned() {
+        let laser = laser(WeaponKind::SingleLaser);
+        let bounds = Vec2::new(400.0, 200.0);
+        let position = Vec3::new(0.0, 101.0, 1.0);
+        assert_eq!(
+            laser.despawn_reason(position, bounds, 10.0),
+            Some(LaserDespawnReason::OutOfBounds)
+        );
+    }
+
+    #[test]
+    fn a_remote_laser_is_backdated_by_its_age() {
+        let laser = LaserData::from_remote(&remote_state(50.0, Some(0.0), 7.5), 100.0).unwrap();
+        assert_eq!(laser.spawned_at, 92.5);
+        assert_eq!(laser.start_pos, Vec3::new(0.0, 0.0, 1.0));
+        let position = Vec3::new(60.0, 0.0, 1.0);
+        assert_eq!(laser.despawn_reason(position, BOUNDS, 100.4), None);
+        assert_eq!(
+            laser.despawn_reason(position, BOUNDS, 100.6),
+            Some(LaserDespawnReason::Lifetime)
+        );
+        // measured from where it was shot, not from where it was received
+        let position = Vec3::new(LASER_MAX_RANGE + 1.0, 0.0, 1.0);
+        assert_eq!(
+            laser.despawn_reason(position, BOUNDS, 100.0),
+            Some(LaserDespawnReason::Range)
+        );
+    }
+
+    #[test]
+    fn an_older_remote_laser_starts_where_it_was_received() {
+        let laser = LaserData::from_remote(&remote_state(50.0, None, 0.0), 100.0).unwrap();
+        assert_eq!(laser.spawned_at, 100.0);
+        assert_eq!(laser.start_pos, Vec3::new(50.0, 0.0, 1.0));
+    }
+
+    #[test]
+    fn a_remote_laser_without_a_valid_uuid_is_dropped() {
+        let state = PlayerLaserSerializedData {
+            uuid: "not-a-uuid".to_string(),
+            ..remote_state(50.0, None, 0.0)
+        };
+        assert!(LaserData::from_remote(&state, 100.0).is_none());
+    }
+
+    #[test]
+    fn a_laser_without_auto_despawn_is_kept() {
+        let laser = laser(WeaponKind::SingleLaser);
+        let position = Vec3::new(BOUNDS.x, BOUNDS.y, 1.0);
+        let now = 10.0 + 2.0 * LASER_MAX_LIFETIME;
+        let kept = Movable {
+            auto_despawn: false,
+        };
+        assert_eq!(
+            kept.laser_despawn_reason(&laser, position, BOUNDS, now),
+            None
+        );
+        let despawned = Movable { auto_despawn: true };
+        assert_eq!(
+            despawned.laser_despawn_reason(&laser, position, BOUNDS, now),
+            Some(LaserDespawnReason::Range)
+        );
+    }
 }
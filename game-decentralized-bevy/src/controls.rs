@@ -0,0 +1,188 @@
+//! Input-action layer: keyboard and gamepad input are merged into the [`InputActions`] resource,
+//! which the local player systems read instead of querying devices directly. Keyboard keys come
+//! from the rebindable [`InputBindings`].
+use bevy::input::gamepad::{GamepadEvent, GamepadEventType};
+use bevy::input::InputSystem;
+use bevy::prelude::*;
+
+use crate::bindings::{
+    instructions_text, key_code_from_name, load_bindings, save_bindings, serialize_bindings,
+};
+use crate::components::InstructionsText;
+use crate::resources::{ActiveGamepad, InputAction, InputActions, InputBindings};
+use crate::wasm::{INPUT_BINDINGS, INPUT_REBIND_REQUESTS};
+
+// analog stick values with a smaller magnitude are ignored
+const GAMEPAD_STICK_DEAD_ZONE: f32 = 0.2;
+
+#[derive(SystemLabel, Debug, Clone, PartialEq, Eq, Hash)]
+pub enum ControlsLabel {
+    Bindings,
+    Gamepads,
+    Actions,
+}
+
+pub struct ControlsPlugin;
+
+impl Plugin for ControlsPlugin {
+    fn build(
+        &self,
+        app: &mut App,
+    ) {
+        app.init_resource::<InputActions>();
+        app.init_resource::<ActiveGamepad>();
+        app.insert_resource(load_bindings());
+        app.add_system_to_stage(
+            CoreStage::PreUpdate,
+            input_bindings_system.label(ControlsLabel::Bindings),
+        );
+        // devices are sampled every frame (the game logic runs on a fixed timestep)
+        app.add_system_to_stage(
+            CoreStage::PreUpdate,
+            gamepad_connection_system
+                .label(ControlsLabel::Gamepads)
+                .after(InputSystem),
+        );
+        app.add_system_to_stage(
+            CoreStage::PreUpdate,
+            input_actions_system
+                .label(ControlsLabel::Actions)
+                .after(ControlsLabel::Gamepads)
+                .after(ControlsLabel::Bindings),
+        );
+    }
+}
+
+/// Applies the rebinding requests from js, then persists and publishes the bindings and refreshes
+/// the on-screen instructions whenever they change
+fn input_bindings_system(
+    mut bindings: ResMut<InputBindings>,
+    mut instructions_query: Query<&mut Text, With<InstructionsText>>,
+) {
+    let rebind_requests = INPUT_REBIND_REQUESTS.with(|requests| requests.take());
+    for (action, key) in rebind_requests.iter() {
+        match (InputAction::from_name(action), key_code_from_name(key)) {
+            (Some(action), Some(key)) => {
+                bindings.rebind(action, key);
+            }
+            _ => warn!("Invalid rebinding request {:?} -> {:?}", action, key),
+        }
+    }
+    if !rebind_requests.is_empty() {
+        save_bindings(&bindings);
+    }
+
+    if bindings.is_changed() {
+        INPUT_BINDINGS.with(|published| {
+            *published.borrow_mut() = Some(serialize_bindings(&bindings));
+        });
+        let instructions = instructions_text(&bindings);
+        for mut text in instructions_query.iter_mut() {
+            if let Some(section) = text.sections.first_mut() {
+                section.value = instructions.clone();
+            }
+        }
+    }
+}
+
+/// Tracks gamepad hot-plugging: the first connected gamepad drives the ship, and another one
+/// takes over when it gets disconnected
+fn gamepad_connection_system(
+    gamepads: Res<Gamepads>,
+    mut active_gamepad: ResMut<ActiveGamepad>,
+    mut gamepad_events: EventReader<GamepadEvent>,
+) {
+    for event in gamepad_events.iter() {
+        match event.event_type {
+            GamepadEventType::Connected => {
+                info!("Gamepad {:?} connected", event.gamepad);
+                if active_gamepad.0.is_none() {
+                    active_gamepad.0 = Some(event.gamepad);
+                }
+            }
+            GamepadEventType::Disconnected => {
+                info!("Gamepad {:?} disconnected", event.gamepad);
+                if active_gamepad.0 == Some(event.gamepad) {
+                    active_gamepad.0 = gamepads
+                        .iter()
+                        .map(|gamepad| gamepad.clone())
+                        .find(|gamepad| *gamepad != event.gamepad);
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+fn input_actions_system(
+    keyboard_input: Res<Input<KeyCode>>,
+    bindings: Res<InputBindings>,
+    active_gamepad: Res<ActiveGamepad>,
+    gamepad_axes: Res<Axis<GamepadAxis>>,
+    gamepad_buttons: Res<Input<GamepadButton>>,
+    mut input_actions: ResMut<InputActions>,
+) {
+    // keyboard
+    let mut rotation = 0.0;
+    if bindings.pressed(InputAction::RotateLeft, &keyboard_input) {
+        rotation += 1.0;
+    }
+    if bindings.pressed(InputAction::RotateRight, &keyboard_input) {
+        rotation -= 1.0;
+    }
+    let mut thrust: f32 = if bindings.pressed(InputAction::Thrust, &keyboard_input) {
+        1.0
+    } else {
+        0.0
+    };
+    let mut brake = bindings.pressed(InputAction::Brake, &keyboard_input);
+    let mut fire = bindings.just_pressed(InputAction::Fire, &keyboard_input);
+    let mut fire_held = bindings.pressed(InputAction::Fire, &keyboard_input);
+    let mut switch_weapon = bindings.just_pressed(InputAction::SwitchWeapon, &keyboard_input);
+    input_actions.boost = bindings.pressed(InputAction::Boost, &keyboard_input);
+    input_actions.toggle_scoreboard =
+        bindings.just_pressed(InputAction::ToggleScoreboard, &keyboard_input);
+    input_actions.toggle_diagnostics =
+        bindings.just_pressed(InputAction::ToggleDiagnostics, &keyboard_input);
+
+    // gamepad
+    if let Some(gamepad) = active_gamepad.0 {
+        let stick_x = gamepad_axes
+            .get(GamepadAxis::new(gamepad, GamepadAxisType::LeftStickX))
+            .unwrap_or(0.0);
+        if stick_x.abs() > GAMEPAD_STICK_DEAD_ZONE {
+            // rescale so the rotation starts from zero at the dead zone edge
+            let magnitude =
+                (stick_x.abs() - GAMEPAD_STICK_DEAD_ZONE) / (1.0 - GAMEPAD_STICK_DEAD_ZONE);
+            rotation -= stick_x.signum() * magnitude;
+        }
+
+        // stick up thrusts, stick down brakes
+        let stick_y = gamepad_axes
+            .get(GamepadAxis::new(gamepad, GamepadAxisType::LeftStickY))
+            .unwrap_or(0.0);
+        if stick_y > GAMEPAD_STICK_DEAD_ZONE {
+            thrust =
+                thrust.max((stick_y - GAMEPAD_STICK_DEAD_ZONE) / (1.0 - GAMEPAD_STICK_DEAD_ZONE));
+        } else if stick_y < -GAMEPAD_STICK_DEAD_ZONE {
+            brake = true;
+        }
+
+        let fire_buttons = [
+            GamepadButton::new(gamepad, GamepadButtonType::RightTrigger2),
+            GamepadButton::new(gamepad, GamepadButtonType::South),
+        ];
+        fire |= gamepad_buttons.any_just_pressed(fire_buttons);
+        fire_held |= gamepad_buttons.any_pressed(fire_buttons);
+        switch_weapon |=
+            gamepad_buttons.just_pressed(GamepadButton::new(gamepad, GamepadButtonType::North));
+    }
+
+    input_actions.rotation = rotation.clamp(-1.0, 1.0);
+    input_actions.thrust = thrust.clamp(0.0, 1.0);
+    input_actions.brake = brake;
+    input_actions.fire_held = fire_held;
+    // keep a pending shot or weapon switch until the fixed timestep consumes it
+    input_actions.fire |= fire;
+    input_actions.switch_weapon |= switch_weapon;
+}
@@ -0,0 +1,346 @@
+//! Optional deterministic simulation core: while enabled, the local ship and the lasers carry
+//! their kinematic state in fixed-point numbers ([`FixedShip`], [`FixedLaser`]), which the fixed
+//! timestep systems step instead of the float transforms. The transforms are only written from the
+//! fixed state, so the same inputs give bit-identical positions on every client, a prerequisite
+//! for lockstep or chain-verified movement. The float state is quantized once, when the fixed
+//! components are attached.
+use bevy::prelude::*;
+
+use crate::components::{LocalLaser, LocalPlayer, RemoteLaser, Velocity};
+use crate::fixed::{Fixed, FixedVec2};
+use crate::physics::ShipControls;
+use crate::resources::DeterministicSimulation;
+use crate::wasm::DETERMINISTIC_SIMULATION_REQUEST;
+use crate::{
+    BOUNDS, PLAYER_ANGULAR_ACCELERATION, PLAYER_ANGULAR_DRAG, PLAYER_BOOST_FACTOR,
+    PLAYER_BRAKE_DECELERATION, PLAYER_LINEAR_DRAG, PLAYER_THRUST_ACCELERATION,
+    POWER_UP_SPEED_FACTOR,
+};
+
+pub struct DeterministicSimulationPlugin;
+
+impl Plugin for DeterministicSimulationPlugin {
+    fn build(
+        &self,
+        app: &mut App,
+    ) {
+        app.init_resource::<DeterministicSimulation>();
+        app.add_system(deterministic_simulation_requests_system);
+        app.add_system(fixed_kinematics_system);
+    }
+}
+
+/// Fixed-point kinematic state of a ship
+#[derive(Component, Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct FixedShip {
+    pub position: FixedVec2,
+    pub velocity: FixedVec2,
+    /// rotation around the z axis, in radians within [-π, π)
+    pub angle: Fixed,
+    /// angular velocity, in radians per second
+    pub angular: Fixed,
+    /// max linear speed, in units per second
+    pub max_linear: Fixed,
+    /// max angular speed, in radians per second
+    pub max_angular: Fixed,
+}
+
+impl FixedShip {
+    /// Quantizes the float state of a ship
+    pub fn from_float(
+        transform: &Transform,
+        velocity: &Velocity,
+    ) -> FixedShip {
+        FixedShip {
+            position: FixedVec2::new(
+                Fixed::from_f32(transform.translation.x),
+                Fixed::from_f32(transform.translation.y),
+            ),
+            velocity: FixedVec2::new(
+                Fixed::from_f32(velocity.vector.x),
+                Fixed::from_f32(velocity.vector.y),
+            ),
+            angle: Fixed::from_f32(z_angle(transform.rotation)),
+            angular: Fixed::from_f32(velocity.angular),
+            max_linear: Fixed::from_f32(velocity.linear),
+            max_angular: Fixed::from_f32(velocity.rotational),
+        }
+    }
+
+    /// Writes the state back to the float components, which are only used for rendering and for
+    /// the outbound player state
+    pub fn write_float(
+        &self,
+        transform: &mut Transform,
+        velocity: &mut Velocity,
+    ) {
+        transform.translation.x = self.position.x.to_f32();
+        transform.translation.y = self.position.y.to_f32();
+        transform.rotation = Quat::from_rotation_z(self.angle.to_f32());
+        velocity.vector = Vec2::new(self.velocity.x.to_f32(), self.velocity.y.to_f32());
+        velocity.angular = self.angular.to_f32();
+    }
+}
+
+/// Fixed-point kinematic state of a laser, moving straight at a constant speed
+#[derive(Component, Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct FixedLaser {
+    pub position: FixedVec2,
+    /// unit vector of the direction of the laser
+    pub direction: FixedVec2,
+    /// in units per second
+    pub speed: Fixed,
+}
+
+impl FixedLaser {
+    /// A laser shot by a ship, deviated by `spread_angle` radians
+    pub fn shot_by(
+        ship: &FixedShip,
+        spread_angle: f32,
+        speed: f32,
+    ) -> FixedLaser {
+        FixedLaser {
+            position: ship.position,
+            direction: FixedVec2::from_heading(ship.angle + Fixed::from_f32(spread_angle)),
+            speed: Fixed::from_f32(speed),
+        }
+    }
+
+    /// Quantizes the float state of a laser
+    pub fn from_float(
+        transform: &Transform,
+        speed: f32,
+    ) -> FixedLaser {
+        FixedLaser {
+            position: FixedVec2::new(
+                Fixed::from_f32(transform.translation.x),
+                Fixed::from_f32(transform.translation.y),
+            ),
+            direction: FixedVec2::from_heading(Fixed::from_f32(z_angle(transform.rotation))),
+            speed: Fixed::from_f32(speed),
+        }
+    }
+
+    pub fn write_float(
+        &self,
+        transform: &mut Transform,
+    ) {
+        transform.translation.x = self.position.x.to_f32();
+        transform.translation.y = self.position.y.to_f32();
+    }
+}
+
+// rotation around the z axis of a quaternion, in radians
+fn z_angle(rotation: Quat) -> f32 {
+    2.0 * rotation.z.atan2(rotation.w)
+}
+
+/// Advances a ship by `dt` seconds: the fixed-point counterpart of `physics::step_ship`
+pub fn step_fixed_ship(
+    ship: &mut FixedShip,
+    controls: &ShipControls,
+    dt: Fixed,
+) {
+    let mut boost_factor = Fixed::ONE;
+    if controls.boost {
+        boost_factor *= Fixed::from_f32(PLAYER_BOOST_FACTOR);
+    }
+    if controls.speed_boost {
+        boost_factor *= Fixed::from_f32(POWER_UP_SPEED_FACTOR);
+    }
+
+    // angular velocity: accelerate while rotating, damp otherwise
+    let rotation = Fixed::from_f32(controls.rotation);
+    if rotation != Fixed::ZERO {
+        ship.angular += rotation * Fixed::from_f32(PLAYER_ANGULAR_ACCELERATION) * dt;
+    } else {
+        ship.angular *= (Fixed::ONE - Fixed::from_f32(PLAYER_ANGULAR_DRAG) * dt).max(Fixed::ZERO);
+    }
+    ship.angular = ship.angular.clamp(-ship.max_angular, ship.max_angular);
+    ship.angle = (ship.angle + ship.angular * dt).wrap_angle();
+
+    // linear velocity: thrust along the heading, brake against the motion, then drag
+    let heading = FixedVec2::from_heading(ship.angle);
+    let thrust = Fixed::from_f32(controls.thrust);
+    ship.velocity +=
+        heading.scale(thrust * Fixed::from_f32(PLAYER_THRUST_ACCELERATION) * boost_factor * dt);
+    if controls.brake {
+        let speed = ship.velocity.length();
+        let braked_speed =
+            (speed - Fixed::from_f32(PLAYER_BRAKE_DECELERATION) * dt).max(Fixed::ZERO);
+        ship.velocity = ship.velocity.normalize_or_zero().scale(braked_speed);
+    }
+    ship.velocity = ship
+        .velocity
+        .scale((Fixed::ONE - Fixed::from_f32(PLAYER_LINEAR_DRAG) * dt).max(Fixed::ZERO));
+    ship.velocity = ship
+        .velocity
+        .clamp_length_max(ship.max_linear * boost_factor);
+
+    ship.position += ship.velocity.scale(dt);
+
+    // limit the movement within the screen, stopping the motion against the edges
+    let extent_x = Fixed::from_f32(BOUNDS.x / 2.0);
+    let extent_y = Fixed::from_f32(BOUNDS.y / 2.0);
+    let clamped_x = ship.position.x.clamp(-extent_x, extent_x);
+    let clamped_y = ship.position.y.clamp(-extent_y, extent_y);
+    if clamped_x != ship.position.x {
+        ship.velocity.x = Fixed::ZERO;
+    }
+    if clamped_y != ship.position.y {
+        ship.velocity.y = Fixed::ZERO;
+    }
+    ship.position = FixedVec2::new(clamped_x, clamped_y);
+}
+
+/// Advances a laser by `dt` seconds
+pub fn step_fixed_laser(
+    laser: &mut FixedLaser,
+    dt: Fixed,
+) {
+    laser.position += laser.direction.scale(laser.speed * dt);
+}
+
+/// Applies the deterministic simulation toggles requested from js
+fn deterministic_simulation_requests_system(mut simulation: ResMut<DeterministicSimulation>) {
+    if let Some(enabled) = DETERMINISTIC_SIMULATION_REQUEST.with(|request| request.take()) {
+        simulation.enabled = enabled;
+    }
+}
+
+/// Attaches the fixed-point state to the local ship and the lasers while the deterministic
+/// simulation is enabled, and detaches it otherwise
+fn fixed_kinematics_system(
+    mut commands: Commands,
+    simulation: Res<DeterministicSimulation>,
+    ships_query: Query<(Entity, &Transform, &Velocity, Option<&FixedShip>), With<LocalPlayer>>,
+    lasers_query: Query<
+        (Entity, &Transform, &Velocity, Option<&FixedLaser>),
+        Or<(With<LocalLaser>, With<RemoteLaser>)>,
+    >,
+) {
+    for (entity, transform, velocity, fixed_ship) in ships_query.iter() {
+        match (simulation.enabled, fixed_ship.is_some()) {
+            (true, false) => {
+                commands
+                    .entity(entity)
+                    .insert(FixedShip::from_float(transform, velocity));
+            }
+            (false, true) => {
+                commands.entity(entity).remove::<FixedShip>();
+            }
+            _ => {}
+        }
+    }
+    for (entity, transform, velocity, fixed_laser) in lasers_query.iter() {
+        match (simulation.enabled, fixed_laser.is_some()) {
+            (true, false) => {
+                commands
+                    .entity(entity)
+                    .insert(FixedLaser::from_float(transform, velocity.linear));
+            }
+            (false, true) => {
+                commands.entity(entity).remove::<FixedLaser>();
+            }
+            _ => {}
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{PLAYER_LINEAR_MOVEMENT_SPEED, PLAYER_LINEAR_ROTATION_SPEED, TIME_STEP};
+
+    fn new_ship() -> FixedShip {
+        FixedShip {
+            position: FixedVec2::new(Fixed::from_int(-120), Fixed::from_int(40)),
+            velocity: FixedVec2::ZERO,
+            angle: Fixed::ZERO,
+            angular: Fixed::ZERO,
+            max_linear: Fixed::from_f32(PLAYER_LINEAR_MOVEMENT_SPEED),
+            max_angular: Fixed::from_f32(f32::to_radians(PLAYER_LINEAR_ROTATION_SPEED)),
+        }
+    }
+
+    // scripted inputs: thrust while turning, boost, brake, then drift
+    fn controls_at(step: u32) -> ShipControls {
+        ShipControls {
+            rotation: match step % 200 {
+                0..=59 => 1.0,
+                60..=99 => -0.5,
+                _ => 0.0,
+            },
+            thrust: if step % 150 < 100 { 1.0 } else { 0.0 },
+            brake: step % 150 >= 130,
+            boost: step % 300 < 50,
+            speed_boost: step >= 600,
+        }
+    }
+
+    fn run_simulation(steps: u32) -> (FixedShip, Vec<FixedLaser>) {
+        let dt = Fixed::from_f32(TIME_STEP);
+        let mut ship = new_ship();
+        let mut lasers = vec![];
+        for step in 0..steps {
+            step_fixed_ship(&mut ship, &controls_at(step), dt);
+            if step % 25 == 0 {
+                lasers.push(FixedLaser::shot_by(&ship, 0.1, 45.0));
+            }
+            for laser in lasers.iter_mut() {
+                step_fixed_laser(laser, dt);
+            }
+        }
+        (ship, lasers)
+    }
+
+    #[test]
+    fn simulation_is_bit_identical_across_runs() {
+        let first = run_simulation(1000);
+        let second = run_simulation(1000);
+        assert_eq!(first, second);
+    }
+
+    #[test]
+    fn simulation_matches_the_recorded_state() {
+        // recorded once, any change of these values breaks the compatibility between clients
+        let (ship, lasers) = run_simulation(1000);
+        assert_eq!(
+            ship.position,
+            FixedVec2::new(Fixed(-13_235_038), Fixed(16_384_000))
+        );
+        assert_eq!(ship.velocity, FixedVec2::new(Fixed(385_203), Fixed(0)));
+        assert_eq!(ship.angle, Fixed(-4_600));
+        assert_eq!(ship.angular, Fixed(0));
+        assert_eq!(lasers.len(), 40);
+        assert_eq!(
+            lasers.last().map(|laser| laser.position),
+            Some(FixedVec2::new(Fixed(-13_454_229), Fixed(17_858_225)))
+        );
+    }
+
+    #[test]
+    fn ship_stays_within_bounds() {
+        let (ship, _) = run_simulation(3000);
+        assert!(ship.position.x.abs() <= Fixed::from_f32(BOUNDS.x / 2.0));
+        assert!(ship.position.y.abs() <= Fixed::from_f32(BOUNDS.y / 2.0));
+    }
+
+    #[test]
+    fn sin_cos_match_the_float_functions() {
+        for degrees in -720..=720 {
+            let radians = (degrees as f32).to_radians();
+            let (sin, cos) = Fixed::from_f32(radians).sin_cos();
+            assert!(
+                (sin.to_f32() - radians.sin()).abs() < 1e-3,
+                "sin {}",
+                degrees
+            );
+            assert!(
+                (cos.to_f32() - radians.cos()).abs() < 1e-3,
+                "cos {}",
+                degrees
+            );
+        }
+    }
+}
@@ -0,0 +1,162 @@
+//! Network diagnostics: the inbound updates are recorded into the [`NetworkMetrics`] while being
+//! decoded, summarized every second with the fps and the entity counts, published to js and shown
+//! by a toggleable overlay.
+use bevy::diagnostic::{Diagnostics, FrameTimeDiagnosticsPlugin};
+use bevy::prelude::*;
+use bevy_debug_text_overlay::screen_print;
+
+use crate::components::{Collectible, LocalLaser, LocalPlayer, RemoteLaser, RemotePlayer};
+use crate::particles::Particle;
+use crate::resources::{DiagnosticsOverlay, InputActions, NetworkMetrics};
+use crate::wasm::{DIAGNOSTICS_VISIBILITY_REQUEST, NETWORK_METRICS};
+
+// length of the window over which the update rates are measured, in seconds
+const METRICS_WINDOW: f64 = 1.0;
+// characters of the player uuids shown by the overlay
+const OVERLAY_UUID_LENGTH: usize = 8;
+
+pub struct NetworkDiagnosticsPlugin;
+
+impl Plugin for NetworkDiagnosticsPlugin {
+    fn build(
+        &self,
+        app: &mut App,
+    ) {
+        app.add_plugin(FrameTimeDiagnosticsPlugin::default());
+        app.init_resource::<NetworkMetrics>();
+        app.init_resource::<DiagnosticsOverlay>();
+        app.add_system(diagnostics_overlay_toggle_system);
+        // after the gameplay systems, to see the entities spawned and despawned this frame
+        app.add_system_to_stage(CoreStage::PostUpdate, network_metrics_system);
+    }
+}
+
+/// Current unix time in milliseconds, to compare with the chain time of the events
+pub fn unix_time_millis() -> f64 {
+    #[cfg(target_arch = "wasm32")]
+    return js_sys::Date::now();
+    #[cfg(not(target_arch = "wasm32"))]
+    return std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map(|duration| duration.as_secs_f64() * 1000.0)
+        .unwrap_or_default();
+}
+
+fn diagnostics_overlay_toggle_system(
+    input_actions: Res<InputActions>,
+    mut overlay: ResMut<DiagnosticsOverlay>,
+) {
+    if input_actions.toggle_diagnostics {
+        overlay.visible = !overlay.visible;
+    }
+    if let Some(visible) = DIAGNOSTICS_VISIBILITY_REQUEST.with(|request| request.take()) {
+        overlay.visible = visible;
+    }
+}
+
+/// Summarizes the metrics every second and publishes them to js, and prints the overlay while it
+/// is visible
+fn network_metrics_system(
+    time: Res<Time>,
+    mut window_start: Local<f64>,
+    mut metrics: ResMut<NetworkMetrics>,
+    overlay: Res<DiagnosticsOverlay>,
+    diagnostics: Res<Diagnostics>,
+    remote_players_query: Query<&RemotePlayer>,
+    entities_query: Query<()>,
+    players_query: Query<(), Or<(With<LocalPlayer>, With<RemotePlayer>)>>,
+    lasers_query: Query<(), Or<(With<LocalLaser>, With<RemoteLaser>)>>,
+    collectibles_query: Query<(), With<Collectible>>,
+    particles_query: Query<(), With<Particle>>,
+) {
+    let now = time.seconds_since_startup();
+    let elapsed = now - *window_start;
+    if elapsed >= METRICS_WINDOW {
+        *window_start = now;
+
+        let window_counts = std::mem::take(&mut metrics.window_counts);
+        metrics.updates_per_second = window_counts
+            .into_iter()
+            .map(|(operation, count)| (operation, count as f64 / elapsed))
+            .collect();
+
+        // forget the players which left
+        let remote_players = remote_players_query
+            .iter()
+            .map(|remote_player| remote_player.0.as_str())
+            .collect::<Vec<&str>>();
+        metrics
+            .player_updated_at
+            .retain(|uuid, _| remote_players.contains(&uuid.as_str()));
+        metrics.player_update_ages = metrics
+            .player_updated_at
+            .iter()
+            .map(|(uuid, updated_at)| (uuid.clone(), now - updated_at))
+            .collect();
+
+        metrics.fps = diagnostics
+            .get(FrameTimeDiagnosticsPlugin::FPS)
+            .and_then(|fps| fps.average());
+        metrics.entity_counts = [
+            ("total", entities_query.iter().count()),
+            ("players", players_query.iter().count()),
+            ("lasers", lasers_query.iter().count()),
+            ("collectibles", collectibles_query.iter().count()),
+            ("particles", particles_query.iter().count()),
+        ]
+        .into_iter()
+        .map(|(kind, count)| (kind.to_string(), count))
+        .collect();
+
+        let serialized_metrics = serde_json::to_string(&*metrics).unwrap_or_default();
+        NETWORK_METRICS.with(|published| *published.borrow_mut() = serialized_metrics);
+    }
+
+    if overlay.visible {
+        print_overlay(&metrics);
+    }
+}
+
+fn print_overlay(metrics: &NetworkMetrics) {
+    let fps = metrics.fps.unwrap_or_default();
+    let entities = metrics
+        .entity_counts
+        .iter()
+        .map(|(kind, count)| format!("{} {}", kind, count))
+        .collect::<Vec<String>>()
+        .join(", ");
+    screen_print!(sec: 1, col: Color::CYAN, "fps: {fps:.0} | entities: {entities}");
+
+    let rates = metrics
+        .updates_per_second
+        .iter()
+        .map(|(operation, rate)| format!("{} {:.1}", operation, rate))
+        .collect::<Vec<String>>()
+        .join(", ");
+    let queue_depth = metrics.queue_depth;
+    let overflowed = metrics.overflowed_updates;
+    let coalesced = metrics.coalesced_updates;
+    let discarded = metrics.discarded_updates;
+    let decode_errors = metrics.decode_errors;
+    let unknown_operations = metrics.unknown_operations;
+    let stale_updates = metrics.stale_updates;
+    screen_print!(sec: 1, col: Color::CYAN, "updates/s: {rates} | decode errors: {decode_errors} | unknown: {unknown_operations} | stale: {stale_updates}");
+    screen_print!(sec: 1, col: Color::CYAN, "queue: {queue_depth} | dropped: {overflowed} | coalesced: {coalesced} | discarded: {discarded}");
+
+    let latency = metrics
+        .chain_latency_ms
+        .map(|latency| format!("{:.0} ms", latency))
+        .unwrap_or_else(|| "-".to_string());
+    screen_print!(sec: 1, col: Color::CYAN, "chain latency: {latency}");
+
+    let player_ages = metrics
+        .player_update_ages
+        .iter()
+        .map(|(uuid, age)| {
+            let short_uuid = uuid.chars().take(OVERLAY_UUID_LENGTH).collect::<String>();
+            format!("{} {:.1}s", short_uuid, age)
+        })
+        .collect::<Vec<String>>()
+        .join(", ");
+    screen_print!(sec: 1, col: Color::CYAN, "last player updates: {player_ages}");
+}
@@ -8,4 +8,16 @@ pub enum ClientError {
     MissingOperationKey,
     #[error("unknown data store error")]
     UnknownOperationReceived,
+    #[error("Undecodable event data: {0}")]
+    UndecodableEventData(String),
+}
+
+#[derive(Error, Debug, Clone, PartialEq, Eq)]
+pub enum AddressError {
+    #[error("Unknown prefix of the address `{0}`")]
+    InvalidPrefix(String),
+    #[error("Invalid base58check encoding of the address `{0}`")]
+    InvalidEncoding(String),
+    #[error("Invalid payload size of the address `{0}`")]
+    InvalidSize(String),
 }
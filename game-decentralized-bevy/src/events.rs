@@ -1,10 +1,38 @@
-use bevy::prelude::{Quat, Vec3};
+use bevy::prelude::{Entity, Quat, Vec2, Vec3};
+use bevy::reflect::Uuid;
 use serde::{Deserialize, Serialize};
+
+use crate::address::MassaAddress;
+use crate::powerups::CollectibleKind;
+use crate::weapons::WeaponKind;
 /// Event being sent out when the own player moves
 #[derive(Debug)]
 pub struct PlayerMoved {
     pub pos: Vec3,
     pub rot: Quat,
+    pub vel: Vec2,
+}
+
+/// Why a laser was despawned
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum LaserDespawnReason {
+    /// it went farther than the range of its weapon from where it was shot
+    Range,
+    /// it flew for longer than the laser lifetime
+    Lifetime,
+    /// it hit a player
+    Hit,
+    /// it left the screen
+    OutOfBounds,
+}
+
+/// Event being sent out when a laser (local or remote) is despawned
+#[derive(Debug)]
+pub struct LaserDespawned {
+    pub entity: Entity,
+    pub uuid: Uuid,
+    pub player_uuid: String,
+    pub reason: LaserDespawnReason,
 }
 
 // player game events
@@ -20,6 +48,15 @@ pub const TOKEN_COLLECTED: &'static str = "TOKEN_COLLECTED";
 // game lasers events
 pub const LASERS_SHOT: &'static str = "LASERS_SHOT";
 
+// screen (chain space) events
+pub const SCREEN_WIDTH_ADJUSTED: &'static str = "SCREEN_WIDTH_ADJUSTED";
+pub const SCREEN_HEIGHT_ADJUSTED: &'static str = "SCREEN_HEIGHT_ADJUSTED";
+
+// full state events (periodic polls of the contract state), their data is a json array of the
+// player or token events data
+pub const PLAYERS_SNAPSHOT: &'static str = "PLAYERS_SNAPSHOT";
+pub const TOKENS_SNAPSHOT: &'static str = "TOKENS_SNAPSHOT";
+
 // all of these events come from js via polling the blockchain
 #[derive(Clone, Debug, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
@@ -27,19 +64,38 @@ pub struct RemoteCollectibleEventData {
     pub uuid: String,
     pub x: f64,
     pub y: f64,
+    // collectibles sent without a kind are plain tokens
+    #[serde(default)]
+    pub kind: CollectibleKind,
+    // strictly increasing counter of the event source, shared by all its events, absent from older
+    // events (two events of a source never carry the same seq)
+    #[serde(default)]
+    pub seq: Option<u64>,
 }
 
 #[derive(Clone, Debug, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct RemotePlayerEventData {
     pub uuid: String,
-    pub address: String,
+    pub address: MassaAddress,
     pub name: String,
     pub x: f64,
     pub y: f64,
     pub rot: f64,
     pub w: f64,
+    // linear velocity, absent from older player states
+    #[serde(default)]
+    pub vx: f64,
+    #[serde(default)]
+    pub vy: f64,
+    // skin picked by the player, the skin is derived from the address when absent
+    #[serde(default)]
+    pub skin: Option<String>,
     pub r#type: String,
+    // strictly increasing counter of the event source, shared by all its events, absent from older
+    // events (two events of a source never carry the same seq)
+    #[serde(default)]
+    pub seq: Option<u64>,
 }
 
 #[derive(Clone, Debug, Serialize, Deserialize)]
@@ -49,15 +105,30 @@ pub struct CollectedEntityEventData {
     pub player_uuid: String,
     pub value: f64,
     pub time: f64,
+    // strictly increasing counter of the event source, shared by all its events, absent from older
+    // events (two events of a source never carry the same seq)
+    #[serde(default)]
+    pub seq: Option<u64>,
+}
+
+/// The chain time (unix milliseconds) of the timestamped events (lasers shot, token collected,
+/// player moved)
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct TimedEventData {
+    pub time: f64,
 }
 
 #[derive(Clone, Debug, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct PlayerLaserEventData {
-    pub player_address: String,
+    pub player_address: MassaAddress,
     pub player_uuid: String,
     pub lasers_data: String,
     pub time: f64,
+    // strictly increasing counter of the event source, shared by all its events, absent from older
+    // events (two events of a source never carry the same seq)
+    #[serde(default)]
+    pub seq: Option<u64>,
 }
 
 #[derive(Clone, Debug, Serialize, Deserialize)]
@@ -69,4 +140,15 @@ pub struct PlayerLaserSerializedData {
     pub y: f64,
     pub rot: f64,
     pub w: f64,
+    // lasers from older clients carry no weapon
+    #[serde(default)]
+    pub weapon: WeaponKind,
+    // shooting position and seconds since the shot, so every client applies the same range and
+    // lifetime limits (lasers from older clients count from their first known state)
+    #[serde(default)]
+    pub start_x: Option<f64>,
+    #[serde(default)]
+    pub start_y: Option<f64>,
+    #[serde(default)]
+    pub age: f64,
 }
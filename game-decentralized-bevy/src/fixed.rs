@@ -0,0 +1,258 @@
+//! Fixed-point arithmetic for the deterministic simulation: numbers are `i64` with 16 fractional
+//! bits, and every operation (including the square root and the trigonometry) only uses integer
+//! arithmetic, so the results are bit-identical on every platform.
+use std::ops::{Add, AddAssign, Div, Mul, MulAssign, Neg, Sub, SubAssign};
+
+/// A signed fixed-point number with 16 fractional bits (Q47.16)
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Fixed(pub i64);
+
+impl Fixed {
+    pub const FRACTION_BITS: u32 = 16;
+    pub const ZERO: Fixed = Fixed(0);
+    pub const ONE: Fixed = Fixed(1 << Self::FRACTION_BITS);
+    pub const PI: Fixed = Fixed(205_887);
+    pub const FRAC_PI_2: Fixed = Fixed(102_944);
+    pub const TAU: Fixed = Fixed(411_775);
+
+    pub const fn from_int(value: i64) -> Fixed {
+        Fixed(value << Self::FRACTION_BITS)
+    }
+
+    /// Quantizes a float, rounding to the nearest fixed-point number (exact and deterministic: the
+    /// float is scaled by a power of two)
+    pub fn from_f32(value: f32) -> Fixed {
+        Fixed((value as f64 * (1u64 << Self::FRACTION_BITS) as f64).round() as i64)
+    }
+
+    pub fn to_f32(self) -> f32 {
+        (self.0 as f64 / (1u64 << Self::FRACTION_BITS) as f64) as f32
+    }
+
+    pub fn abs(self) -> Fixed {
+        Fixed(self.0.abs())
+    }
+
+    /// Division by an integer, truncated toward zero
+    pub fn div_int(
+        self,
+        divisor: i64,
+    ) -> Fixed {
+        Fixed(self.0 / divisor)
+    }
+
+    /// Square root, rounded down, zero for the negative numbers
+    pub fn sqrt(self) -> Fixed {
+        if self.0 <= 0 {
+            return Fixed::ZERO;
+        }
+        Fixed(isqrt((self.0 as u128) << Self::FRACTION_BITS) as i64)
+    }
+
+    /// The same angle in radians, within [-π, π)
+    pub fn wrap_angle(self) -> Fixed {
+        Fixed((self.0 + Self::PI.0).rem_euclid(Self::TAU.0) - Self::PI.0)
+    }
+
+    /// Sine and cosine of an angle in radians
+    pub fn sin_cos(self) -> (Fixed, Fixed) {
+        // reduce to [-π/2, π/2], where the polynomials are accurate
+        let angle = self.wrap_angle();
+        let (angle, cos_sign) = if angle > Self::FRAC_PI_2 {
+            (Self::PI - angle, -1)
+        } else if angle < -Self::FRAC_PI_2 {
+            (-Self::PI - angle, -1)
+        } else {
+            (angle, 1)
+        };
+
+        // taylor series up to x^9 (sine) and x^10 (cosine), in horner form
+        let square = angle * angle;
+        let term = |divisor: i64, inner: Fixed| Self::ONE - (square * inner).div_int(divisor);
+        let sin = angle * term(6, term(20, term(42, term(72, Self::ONE))));
+        let cos = term(2, term(12, term(30, term(56, term(90, Self::ONE)))));
+        (sin, Fixed(cos.0 * cos_sign))
+    }
+}
+
+// integer square root, rounded down
+fn isqrt(value: u128) -> u128 {
+    let mut remainder = value;
+    let mut root = 0u128;
+    let mut bit = 1u128 << 126;
+    while bit > value {
+        bit >>= 2;
+    }
+    while bit != 0 {
+        if remainder >= root + bit {
+            remainder -= root + bit;
+            root = (root >> 1) + bit;
+        } else {
+            root >>= 1;
+        }
+        bit >>= 2;
+    }
+    root
+}
+
+impl Add for Fixed {
+    type Output = Fixed;
+
+    fn add(
+        self,
+        other: Fixed,
+    ) -> Fixed {
+        Fixed(self.0 + other.0)
+    }
+}
+
+impl Sub for Fixed {
+    type Output = Fixed;
+
+    fn sub(
+        self,
+        other: Fixed,
+    ) -> Fixed {
+        Fixed(self.0 - other.0)
+    }
+}
+
+impl Mul for Fixed {
+    type Output = Fixed;
+
+    // truncated toward zero, so the damped values decay to zero whatever their sign
+    fn mul(
+        self,
+        other: Fixed,
+    ) -> Fixed {
+        Fixed(((self.0 as i128 * other.0 as i128) / (1i128 << Self::FRACTION_BITS)) as i64)
+    }
+}
+
+impl Div for Fixed {
+    type Output = Fixed;
+
+    // truncated toward zero, saturating on a division by zero
+    fn div(
+        self,
+        other: Fixed,
+    ) -> Fixed {
+        if other.0 == 0 {
+            return Fixed(if self.0 < 0 { i64::MIN } else { i64::MAX });
+        }
+        Fixed((((self.0 as i128) << Self::FRACTION_BITS) / other.0 as i128) as i64)
+    }
+}
+
+impl Neg for Fixed {
+    type Output = Fixed;
+
+    fn neg(self) -> Fixed {
+        Fixed(-self.0)
+    }
+}
+
+impl AddAssign for Fixed {
+    fn add_assign(
+        &mut self,
+        other: Fixed,
+    ) {
+        *self = *self + other;
+    }
+}
+
+impl SubAssign for Fixed {
+    fn sub_assign(
+        &mut self,
+        other: Fixed,
+    ) {
+        *self = *self - other;
+    }
+}
+
+impl MulAssign for Fixed {
+    fn mul_assign(
+        &mut self,
+        other: Fixed,
+    ) {
+        *self = *self * other;
+    }
+}
+
+/// A 2D vector of fixed-point numbers
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub struct FixedVec2 {
+    pub x: Fixed,
+    pub y: Fixed,
+}
+
+impl FixedVec2 {
+    pub const ZERO: FixedVec2 = FixedVec2 {
+        x: Fixed::ZERO,
+        y: Fixed::ZERO,
+    };
+
+    pub fn new(
+        x: Fixed,
+        y: Fixed,
+    ) -> FixedVec2 {
+        FixedVec2 { x, y }
+    }
+
+    /// Unit vector of the heading of a ship rotated by `angle` radians (the ships point up, along
+    /// +Y, when not rotated)
+    pub fn from_heading(angle: Fixed) -> FixedVec2 {
+        let (sin, cos) = angle.sin_cos();
+        FixedVec2::new(-sin, cos)
+    }
+
+    pub fn scale(
+        self,
+        factor: Fixed,
+    ) -> FixedVec2 {
+        FixedVec2::new(self.x * factor, self.y * factor)
+    }
+
+    pub fn length(self) -> Fixed {
+        (self.x * self.x + self.y * self.y).sqrt()
+    }
+
+    pub fn normalize_or_zero(self) -> FixedVec2 {
+        let length = self.length();
+        if length == Fixed::ZERO {
+            return FixedVec2::ZERO;
+        }
+        FixedVec2::new(self.x / length, self.y / length)
+    }
+
+    pub fn clamp_length_max(
+        self,
+        max: Fixed,
+    ) -> FixedVec2 {
+        let length = self.length();
+        if length <= max {
+            return self;
+        }
+        self.normalize_or_zero().scale(max)
+    }
+}
+
+impl Add for FixedVec2 {
+    type Output = FixedVec2;
+
+    fn add(
+        self,
+        other: FixedVec2,
+    ) -> FixedVec2 {
+        FixedVec2::new(self.x + other.x, self.y + other.y)
+    }
+}
+
+impl AddAssign for FixedVec2 {
+    fn add_assign(
+        &mut self,
+        other: FixedVec2,
+    ) {
+        *self = *self + other;
+    }
+}
@@ -0,0 +1,148 @@
+//! Rust port of the token-collection geometry used by the game engine smart contract.
+//!
+//! The contract (`gameEngine/utils/rectangle.ts`) decides whether a player collected a token by
+//! intersecting two axis-aligned boxes of fixed size (`PLAYER_BOUNDING_BOX` and
+//! `COLLECTIBLE_BOUNDING_BOX`) centred on the entities' chain coordinates. Chain coordinates are
+//! centred on the screen origin, y pointing up, and span the screen size set via
+//! `setScreenWidth` / `setScreenHeight` (announced with `SCREEN_WIDTH_ADJUSTED` /
+//! `SCREEN_HEIGHT_ADJUSTED`). The entities are spawned at their raw chain coordinates and the
+//! local position is sent back raw, so Bevy positions are chain positions and are compared as is,
+//! whatever the screen size announced by the contract.
+use bevy::math::{Vec2, Vec3};
+
+use crate::{SCREEN_HEIGHT, SCREEN_WIDTH};
+
+// bounding box sizes, in chain units (see `gameEngine/config.ts`)
+pub const PLAYER_BOUNDING_BOX: f32 = 64.0;
+pub const COLLECTIBLE_BOUNDING_BOX: f32 = 50.0;
+
+/// Mirror of the contract's `Rectangle` class
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Rectangle {
+    pub left: f32,
+    pub right: f32,
+    pub top: f32,
+    pub bottom: f32,
+}
+
+impl Rectangle {
+    pub fn new(
+        left: f32,
+        right: f32,
+        top: f32,
+        bottom: f32,
+    ) -> Self {
+        Self {
+            left,
+            right,
+            top,
+            bottom,
+        }
+    }
+
+    /// Builds the square collision box the contract derives from an entity `cbox`
+    pub fn from_cbox(
+        x: f32,
+        y: f32,
+        cbox: f32,
+    ) -> Self {
+        let frame = cbox / 2.0;
+        Self::new(x - frame, x + frame, y + frame, y - frame)
+    }
+}
+
+/// Checks if 2 rectangles intersect or not (port of `_isIntersection`). Touching edges count.
+pub fn is_intersection(
+    r1: &Rectangle,
+    r2: &Rectangle,
+) -> bool {
+    !(r2.left > r1.right || r2.right < r1.left || r2.top < r1.bottom || r2.bottom > r1.top)
+}
+
+/// Whether the contract would consider a player at `player` collecting a token at `collectible`.
+/// Both positions are in chain space.
+pub fn is_token_collected(
+    player: Vec2,
+    collectible: Vec2,
+) -> bool {
+    let player_cbox = Rectangle::from_cbox(player.x, player.y, PLAYER_BOUNDING_BOX);
+    let collectible_cbox =
+        Rectangle::from_cbox(collectible.x, collectible.y, COLLECTIBLE_BOUNDING_BOX);
+    is_intersection(&collectible_cbox, &player_cbox)
+}
+
+/// The chain coordinate space, as configured on the contract (for reference: the positions are
+/// not scaled to it)
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct ChainSpace {
+    pub width: f32,
+    pub height: f32,
+}
+
+impl Default for ChainSpace {
+    fn default() -> Self {
+        Self {
+            width: SCREEN_WIDTH,
+            height: SCREEN_HEIGHT,
+        }
+    }
+}
+
+impl ChainSpace {
+    /// Predicts the contract collection outcome for Bevy positions, which are chain positions
+    pub fn is_token_collected(
+        &self,
+        player: Vec3,
+        collectible: Vec3,
+    ) -> bool {
+        is_token_collected(player.truncate(), collectible.truncate())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // same cases as the contract's `gameEngine.spec.ts`
+    #[test]
+    fn rectangles_intersect_like_the_contract() {
+        let rect1 = Rectangle::new(20.0, 70.0, 100.0, 50.0);
+        let partially_inside = Rectangle::new(10.0, 30.0, 80.0, 10.0);
+        let enclosed = Rectangle::new(60.0, 70.0, 80.0, 50.0);
+        let outside = Rectangle::new(60.0, 70.0, 40.0, 0.0);
+
+        assert!(is_intersection(&rect1, &partially_inside));
+        assert!(is_intersection(&partially_inside, &rect1));
+        assert!(is_intersection(&rect1, &enclosed));
+        assert!(is_intersection(&enclosed, &rect1));
+        assert!(!is_intersection(&rect1, &outside));
+        assert!(!is_intersection(&outside, &rect1));
+    }
+
+    #[test]
+    fn touching_boxes_are_collected() {
+        // half player box + half collectible box
+        let reach = (PLAYER_BOUNDING_BOX + COLLECTIBLE_BOUNDING_BOX) / 2.0;
+        assert!(is_token_collected(Vec2::ZERO, Vec2::new(reach, 0.0)));
+        assert!(!is_token_collected(Vec2::ZERO, Vec2::new(reach + 0.5, 0.0)));
+    }
+
+    #[test]
+    fn resized_chain_space_agrees_with_the_contract() {
+        // the contract compares the raw chain coordinates, whatever the screen size
+        let chain_space = ChainSpace {
+            width: 2000.0,
+            height: 250.0,
+        };
+        let reach = (PLAYER_BOUNDING_BOX + COLLECTIBLE_BOUNDING_BOX) / 2.0;
+        let player = Vec3::new(400.0, -100.0, 1.0);
+        let touching = Vec3::new(400.0 + reach, -100.0, 0.0);
+        let apart = Vec3::new(400.0 + reach + 0.5, -100.0, 0.0);
+        assert_eq!(
+            chain_space.is_token_collected(player, touching),
+            is_token_collected(player.truncate(), touching.truncate())
+        );
+        assert!(chain_space.is_token_collected(player, touching));
+        assert!(!chain_space.is_token_collected(player, apart));
+    }
+}
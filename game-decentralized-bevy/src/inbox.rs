@@ -0,0 +1,324 @@
+//! Inbound update queue between js and the game loop. A queued update is replaced in place by a
+//! newer move (or laser batch) of the same player, the other updates keep their order, and the
+//! queue has a capacity: the moves and laser batches pushed while it is full are dropped and
+//! counted. The other updates (players and tokens added or removed, snapshots) are never dropped,
+//! they take the place of the oldest queued move or laser batch, or go past the capacity.
+//! The queue is generic over the queued update, so its ordering can be tested outside of js.
+use std::collections::VecDeque;
+
+use bevy::utils::HashMap;
+use serde::Deserialize;
+
+use crate::events::{LASERS_SHOT, PLAYER_ADDED, PLAYER_MOVED, PLAYER_REMOVED};
+use crate::rust_js_mappers::{get_key_value_from_obj, map_js_update_operation};
+use crate::wasm::GameEntityUpdate;
+
+/// Key under which queued updates supersede each other
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+enum CoalesceKey {
+    PlayerMoved(String),
+    LasersShot(String),
+}
+
+/// The player an update is about, read without decoding the whole update
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct UpdateSubject {
+    #[serde(default)]
+    uuid: Option<String>,
+    #[serde(default)]
+    player_uuid: Option<String>,
+    #[serde(default)]
+    seq: Option<u64>,
+}
+
+struct InboxEntry<U> {
+    key: Option<CoalesceKey>,
+    seq: Option<u64>,
+    update: U,
+}
+
+pub struct UpdateInbox<U = GameEntityUpdate> {
+    capacity: usize,
+    entries: VecDeque<InboxEntry<U>>,
+    /// position (since the start) of the front entry
+    front_position: u64,
+    /// position of the queued update of each key
+    slots: HashMap<CoalesceKey, u64>,
+    /// moves and laser batches dropped because the queue was full, since the start
+    pub overflowed: u64,
+    /// updates replaced by a newer one, since the start
+    pub coalesced: u64,
+    /// updates older than the queued one of the same key, dropped, since the start
+    pub discarded: u64,
+}
+
+impl UpdateInbox {
+    pub fn push(
+        &mut self,
+        update: GameEntityUpdate,
+    ) {
+        let operation = map_js_update_operation(&update).unwrap_or_default();
+        let data = get_key_value_from_obj::<String>("data", update.as_ref());
+        self.enqueue(&operation, data.as_deref(), update);
+    }
+}
+
+impl<U> UpdateInbox<U> {
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            capacity,
+            entries: VecDeque::new(),
+            front_position: 0,
+            slots: HashMap::new(),
+            overflowed: 0,
+            coalesced: 0,
+            discarded: 0,
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Queues an update given its operation and json data
+    fn enqueue(
+        &mut self,
+        operation: &str,
+        data: Option<&str>,
+        update: U,
+    ) {
+        let subject = data.and_then(|data| serde_json::from_str::<UpdateSubject>(data).ok());
+
+        let event_seq = subject.as_ref().and_then(|subject| subject.seq);
+        let key = match (operation, subject) {
+            (
+                PLAYER_MOVED,
+                Some(UpdateSubject {
+                    uuid: Some(uuid), ..
+                }),
+            ) => Some(CoalesceKey::PlayerMoved(uuid)),
+            (
+                LASERS_SHOT,
+                Some(UpdateSubject {
+                    player_uuid: Some(uuid),
+                    ..
+                }),
+            ) => Some(CoalesceKey::LasersShot(uuid)),
+            (
+                PLAYER_ADDED | PLAYER_REMOVED,
+                Some(UpdateSubject {
+                    uuid: Some(uuid), ..
+                }),
+            ) => {
+                // the later updates of the player must stay after its addition or removal
+                self.slots.remove(&CoalesceKey::PlayerMoved(uuid.clone()));
+                self.slots.remove(&CoalesceKey::LasersShot(uuid));
+                None
+            }
+            _ => None,
+        };
+
+        if let Some(position) = key.as_ref().and_then(|key| self.slots.get(key).copied()) {
+            let entry = &mut self.entries[(position - self.front_position) as usize];
+            // an older update polled late does not replace a newer queued one
+            let is_older =
+                matches!((event_seq, entry.seq), (Some(new), Some(queued)) if new < queued);
+            if is_older {
+                self.discarded += 1;
+            } else {
+                entry.seq = event_seq;
+                entry.update = update;
+                self.coalesced += 1;
+            }
+            return;
+        }
+
+        if self.entries.len() >= self.capacity {
+            // only a move or a laser batch is dropped, a newer one supersedes it
+            if key.is_some() {
+                self.overflowed += 1;
+                return;
+            }
+            // the other updates take the place of the oldest one, when there is one
+            self.evict_oldest_coalescable();
+        }
+
+        if let Some(key) = key.clone() {
+            let position = self.front_position + self.entries.len() as u64;
+            self.slots.insert(key, position);
+        }
+        self.entries.push_back(InboxEntry {
+            key,
+            seq: event_seq,
+            update,
+        });
+    }
+
+    /// Drops the oldest queued move or laser batch, returns false when there is none
+    fn evict_oldest_coalescable(&mut self) -> bool {
+        let index = match self.entries.iter().position(|entry| entry.key.is_some()) {
+            Some(index) => index,
+            None => return false,
+        };
+        let position = self.front_position + index as u64;
+        if let Some(InboxEntry { key: Some(key), .. }) = self.entries.remove(index) {
+            if self.slots.get(&key) == Some(&position) {
+                self.slots.remove(&key);
+            }
+        }
+        // the entries behind moved up by one
+        for slot in self.slots.values_mut() {
+            if *slot > position {
+                *slot -= 1;
+            }
+        }
+        self.overflowed += 1;
+        true
+    }
+
+    /// Takes up to `max` updates, in order
+    pub fn drain(
+        &mut self,
+        max: usize,
+    ) -> Vec<U> {
+        let count = max.min(self.entries.len());
+        let mut updates = Vec::with_capacity(count);
+        for _ in 0..count {
+            let entry = match self.entries.pop_front() {
+                Some(entry) => entry,
+                None => break,
+            };
+            if let Some(key) = entry.key {
+                if self.slots.get(&key) == Some(&self.front_position) {
+                    self.slots.remove(&key);
+                }
+            }
+            self.front_position += 1;
+            updates.push(entry.update);
+        }
+        updates
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::events::{TOKEN_ADDED, TOKEN_REMOVED};
+
+    fn moved(
+        uuid: &str,
+        seq: u64,
+    ) -> String {
+        format!(r#"{{"uuid":"{}","seq":{}}}"#, uuid, seq)
+    }
+
+    fn push(
+        inbox: &mut UpdateInbox<&'static str>,
+        operation: &str,
+        data: String,
+        update: &'static str,
+    ) {
+        inbox.enqueue(operation, Some(data.as_str()), update);
+    }
+
+    #[test]
+    fn a_player_move_coalesces_into_the_newest_one() {
+        let mut inbox = UpdateInbox::new(10);
+        push(&mut inbox, PLAYER_MOVED, moved("a", 1), "a1");
+        push(&mut inbox, PLAYER_MOVED, moved("b", 1), "b1");
+        push(&mut inbox, PLAYER_MOVED, moved("a", 2), "a2");
+
+        // replaced in place, the order of the players is kept
+        assert_eq!(inbox.drain(10), vec!["a2", "b1"]);
+        assert_eq!(inbox.coalesced, 1);
+        assert_eq!(inbox.discarded, 0);
+    }
+
+    #[test]
+    fn a_late_older_move_does_not_replace_the_queued_one() {
+        let mut inbox = UpdateInbox::new(10);
+        push(&mut inbox, PLAYER_MOVED, moved("a", 5), "a5");
+        push(&mut inbox, PLAYER_MOVED, moved("a", 3), "a3");
+
+        assert_eq!(inbox.drain(10), vec!["a5"]);
+        assert_eq!(inbox.coalesced, 0);
+        assert_eq!(inbox.discarded, 1);
+    }
+
+    #[test]
+    fn the_moves_stay_after_the_player_addition_and_removal() {
+        let mut inbox = UpdateInbox::new(10);
+        push(&mut inbox, PLAYER_MOVED, moved("a", 1), "a moved 1");
+        push(&mut inbox, PLAYER_REMOVED, moved("a", 2), "a removed");
+        push(&mut inbox, PLAYER_ADDED, moved("a", 3), "a added");
+        push(&mut inbox, PLAYER_MOVED, moved("a", 4), "a moved 4");
+        push(&mut inbox, PLAYER_MOVED, moved("a", 5), "a moved 5");
+
+        // the moves after the addition only coalesce between themselves
+        assert_eq!(
+            inbox.drain(10),
+            vec!["a moved 1", "a removed", "a added", "a moved 5"]
+        );
+        assert_eq!(inbox.coalesced, 1);
+    }
+
+    #[test]
+    fn a_drained_move_is_not_replaced() {
+        let mut inbox = UpdateInbox::new(10);
+        push(&mut inbox, PLAYER_MOVED, moved("a", 1), "a1");
+        assert_eq!(inbox.drain(10), vec!["a1"]);
+
+        push(&mut inbox, PLAYER_MOVED, moved("a", 2), "a2");
+        assert_eq!(inbox.drain(10), vec!["a2"]);
+        assert_eq!(inbox.coalesced, 0);
+    }
+
+    #[test]
+    fn the_updates_pushed_at_capacity_overflow() {
+        let mut inbox = UpdateInbox::new(2);
+        push(&mut inbox, PLAYER_MOVED, moved("a", 1), "a1");
+        push(&mut inbox, PLAYER_MOVED, moved("b", 1), "b1");
+        push(&mut inbox, PLAYER_MOVED, moved("c", 1), "c1");
+        // a queued key still coalesces when full
+        push(&mut inbox, PLAYER_MOVED, moved("a", 2), "a2");
+
+        assert_eq!(inbox.len(), 2);
+        assert_eq!(inbox.overflowed, 1);
+        assert_eq!(inbox.coalesced, 1);
+        assert_eq!(inbox.drain(1), vec!["a2"]);
+        assert_eq!(inbox.drain(10), vec!["b1"]);
+        assert!(inbox.is_empty());
+    }
+
+    #[test]
+    fn a_removal_pushed_at_capacity_takes_the_place_of_a_move() {
+        let mut inbox = UpdateInbox::new(3);
+        push(&mut inbox, PLAYER_ADDED, moved("a", 1), "a added");
+        push(&mut inbox, PLAYER_MOVED, moved("b", 1), "b1");
+        push(&mut inbox, PLAYER_MOVED, moved("c", 1), "c1");
+        push(&mut inbox, TOKEN_REMOVED, moved("t", 1), "t removed");
+
+        assert_eq!(inbox.len(), 3);
+        assert_eq!(inbox.overflowed, 1);
+        // the slots behind the dropped move still coalesce
+        push(&mut inbox, PLAYER_MOVED, moved("c", 2), "c2");
+        assert_eq!(inbox.coalesced, 1);
+        assert_eq!(inbox.drain(10), vec!["a added", "c2", "t removed"]);
+    }
+
+    #[test]
+    fn a_removal_pushed_into_a_queue_without_moves_is_kept() {
+        let mut inbox = UpdateInbox::new(2);
+        push(&mut inbox, PLAYER_ADDED, moved("a", 1), "a added");
+        push(&mut inbox, TOKEN_ADDED, moved("t", 1), "t added");
+        push(&mut inbox, PLAYER_REMOVED, moved("a", 2), "a removed");
+
+        assert_eq!(inbox.overflowed, 0);
+        assert_eq!(inbox.drain(10), vec!["a added", "t added", "a removed"]);
+    }
+}
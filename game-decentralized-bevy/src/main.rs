@@ -1,50 +1,105 @@
 #![allow(unused)] // silence unused warnings while exploring (to comment out)
 
 use crate::components::ExplosionToSpawn;
+use crate::reconcile::{reconcile_collectibles, reconcile_players};
 use crate::resources::{RemoteGamePlayerState, RemoteLaserState};
-use crate::utils::{inplace_intersection, spawn_laser_closure, spawn_player_name_text2d_entity};
+use crate::utils::{keyed_diff, spawn_laser_closure, spawn_player_name_text2d_entity};
+use activity::{capped_fixed_step, PageActivityPlugin};
 use anyhow::{Context, Result};
-use bevy::diagnostic::{FrameTimeDiagnosticsPlugin, LogDiagnosticsPlugin};
+use audio::{GameAudioPlugin, PlaySound, SoundEffect};
+use bevy::diagnostic::LogDiagnosticsPlugin;
 use bevy::math::Vec3Swizzles;
 use bevy::reflect::Uuid;
 use bevy::sprite::collide_aabb::collide;
 use bevy::text::Text2dBounds;
 use bevy::utils::HashMap;
 use bevy::window::PresentMode;
-use bevy::{math::Vec2, prelude::*, time::FixedTimestep};
-use bevy_debug_text_overlay::{screen_print, OverlayPlugin};
+use bevy::{math::Vec2, prelude::*};
+use bevy_debug_text_overlay::OverlayPlugin;
+use bindings::instructions_text;
 use components::{
-    AnimateNameTranslation, Collectible, Explosion, ExplosionTimer, LaserData, LocalLaser,
-    LocalPlayer, Movable, RemoteLaser, RemotePlayer, SpriteSize, Velocity,
+    Collectible, Explosion, ExplosionTimer, Health, LaserData, LocalLaser, LocalPlayer, Movable,
+    PlayerTag, RemoteLaser, RemotePlayer, SpriteSize, TagBar, Velocity,
 };
+use controls::ControlsPlugin;
+use deterministic::{
+    step_fixed_laser, step_fixed_ship, DeterministicSimulationPlugin, FixedLaser, FixedShip,
+};
+use diagnostics::{unix_time_millis, NetworkDiagnosticsPlugin};
 use errors::ClientError;
-use events::{PlayerLaserEventData, PlayerLaserSerializedData, PlayerMoved};
+use events::{
+    LaserDespawnReason, LaserDespawned, PlayerLaserEventData, PlayerLaserSerializedData,
+    PlayerMoved,
+};
+use fixed::Fixed;
+use geometry::ChainSpace;
 use js_sys::{Array, Function, Map, Object, Reflect, WebAssembly};
+use particles::{spawn_particle_burst, ParticleEffect, ParticleEmitter, ParticlesPlugin};
+use physics::{step_ship, ShipControls};
+use plausibility::{validate_remote_move, MovementValidationPlugin};
+use powerups::{is_power_up_active, power_up_system, CollectibleKind, PowerUp};
 use resources::{
-    CollectedEntity, EntityType, GameTextures, RemoteCollectibleState, RemoteGameState,
+    CollectedEntity, EntityType, GameTextures, InputActions, InputBindings, MovementValidation,
+    NetworkMetrics, PageActivity, PendingCollectibleClaim, RemoteCollectibleState, RemoteGameState,
     RemoteStateType, WinSize,
 };
 use rust_js_mappers::{
-    get_key_value_from_obj, get_value_for_key, map_js_update_to_rust_entity_state,
+    get_key_value_from_obj, get_value_for_key, map_js_update_event_time, map_js_update_operation,
+    map_js_update_to_rust_entity_state,
 };
-use std::collections::{BTreeMap, HashSet};
+use scoreboard::ScoreboardPlugin;
+use skins::PlayerSkin;
+use snapshot::GameSnapshotPlugin;
+use spectator::SpectatorPlugin;
+use states::{local_player_running, world_running, GameStatePlugin};
+use std::collections::{BTreeMap, HashSet, VecDeque};
 use std::str::FromStr;
 use utils::{spawn_collectible_closure, spawn_game_screen_instructions};
 use wasm::{GameEntityUpdate, GAME_ENTITY_UPDATE, LOCAL_PLAYER_LASERS, LOCAL_PLAYER_POSITION};
 use wasm_bindgen::{JsCast, JsValue};
+use weapons::Weapon;
 
+pub mod activity;
+pub mod address;
+pub mod audio;
+pub mod bindings;
 pub mod components;
+pub mod controls;
+pub mod deterministic;
+pub mod diagnostics;
 pub mod errors;
 pub mod events;
+pub mod fixed;
+pub mod geometry;
+pub mod inbox;
+pub mod particles;
+pub mod physics;
+pub mod plausibility;
+pub mod powerups;
+pub mod reconcile;
 pub mod resources;
 pub mod rust_js_mappers;
+pub mod scoreboard;
+pub mod skins;
+pub mod snapshot;
+pub mod spectator;
+pub mod states;
+pub mod touch;
 pub mod utils;
 pub mod wasm;
+pub mod weapons;
 
 // global settings
 
 // timestep (fps)
 const TIME_STEP: f32 = 1.0 / 50.0;
+// max steps run in one frame to catch up (e.g. after a hidden tab), the time beyond is dropped
+const MAX_CATCH_UP_STEPS: u32 = 5;
+
+// inbound updates
+const INBOUND_QUEUE_CAPACITY: usize = 512; // moves and laser batches pushed by js beyond it are dropped
+const MAX_UPDATES_PER_TICK: usize = 64; // updates applied per fixed step
+const MAX_REMOVED_SEQUENCES: usize = 256; // last sequences of the removed entities kept against late updates
 
 // screen
 const SCREEN_WIDTH: f32 = 1000.0;
@@ -52,18 +107,48 @@ const SCREEN_HEIGHT: f32 = 500.0;
 const BOUNDS: Vec2 = Vec2::from_array([SCREEN_WIDTH, SCREEN_HEIGHT]);
 
 // player speeds
-const PLAYER_LINEAR_MOVEMENT_SPEED: f32 = 25.0; // linear speed in meters per second
-const PLAYER_LINEAR_ROTATION_SPEED: f32 = 300.0; // rotation speed in radians per second
+const PLAYER_LINEAR_MOVEMENT_SPEED: f32 = 50.0; // max linear speed in meters per second
+const PLAYER_LINEAR_ROTATION_SPEED: f32 = 300.0; // max rotation speed in degrees per second
+const PLAYER_BOOST_FACTOR: f32 = 2.0; // max speed and thrust multiplier while boosting
+
+// player health
+const PLAYER_MAX_HEALTH: f32 = 100.0;
+
+// player accelerations
+const PLAYER_THRUST_ACCELERATION: f32 = 40.0; // in meters per second squared
+const PLAYER_BRAKE_DECELERATION: f32 = 60.0; // in meters per second squared
+const PLAYER_LINEAR_DRAG: f32 = 0.5; // fraction of the linear velocity lost per second
+const PLAYER_ANGULAR_ACCELERATION: f32 = 20.0; // in radians per second squared
+const PLAYER_ANGULAR_DRAG: f32 = 8.0; // fraction of the angular velocity lost per second
+
+// plausibility checks of the remote moves
+const PLAUSIBILITY_TOLERANCE: f32 = 1.5; // margin over the movement model limits (lag, rounding)
+const PLAUSIBILITY_MIN_ELAPSED: f64 = 0.5; // closer moves are checked as if that far apart, in seconds
+const PLAUSIBILITY_FLAG_DURATION: f64 = 10.0; // seconds a player stays flagged after an implausible move
 
 // laser speeds
 const LASER_LINEAR_MOVEMENT_SPEED: f32 = 45.0; // linear speed in meters per second
 
-const PLAYER_SPRITES: [(&str, &str); 2] = [
-    ("local", "entities/local.v1.png"),
-    ("remote", "entities/remote.v2.png"),
+// laser limits, applied the same way by every client
+const LASER_MAX_RANGE: f32 = 400.0; // distance from the shooting position (single laser), in units
+const LASER_MAX_LIFETIME: f64 = 8.0; // seconds since the shot
+
+// ship hulls, tinted by the player skins
+const PLAYER_SPRITES: [(&str, &str); 4] = [
+    ("classic", "entities/local.v1.png"),
+    ("raider", "entities/remote.v2.png"),
+    ("phantom", "entities/hull_phantom.png"),
+    ("wraith", "entities/hull_wraith.png"),
 ];
 const PLAYER_SIZE: (f32, f32) = (128., 128.);
 
+// player name tags
+const PLAYER_TAG_OFFSET: Vec3 = Vec3::from_array([0., 55., 2.]); // from the ship, in screen space
+const PLAYER_TAG_BAR_SIZE: (f32, f32) = (50., 5.);
+const PLAYER_TAG_FADE_DISTANCE: (f32, f32) = (300., 700.); // distances to the camera where the fade starts/ends
+
+const FONT: &str = "entities/FiraMono-Medium.ttf";
+
 const BACKGROUND_SPRITE: &str = "entities/galaxy.png";
 const BACKGROUND_SIZE: (f32, f32) = (1000., 50.);
 
@@ -78,6 +163,14 @@ const EXPLOSION_LEN: usize = 16;
 
 const SPRITE_SCALE: f32 = 0.5;
 
+// power-ups
+const POWER_UP_SPEED_FACTOR: f32 = 1.5; // max speed and thrust multiplier of the speed boost
+const POWER_UP_RAPID_FIRE_FACTOR: f32 = 2.0; // weapon cooldown and cooling speed-up of the rapid fire
+const POWER_UP_HEALTH_AMOUNT: f32 = 50.0; // hit points restored by the health power-up
+
+// seconds to wait for the chain to confirm a predicted token collection before restoring it
+const COLLECTIBLE_CLAIM_TIMEOUT: f64 = 10.0;
+
 fn main() {
     let mut app = App::new();
     app.insert_resource(WindowDescriptor {
@@ -94,43 +187,59 @@ fn main() {
         font_size: 16.0,
         ..default()
     });
+    app.add_plugin(ControlsPlugin);
+    app.add_plugin(GameAudioPlugin);
+    app.add_plugin(ParticlesPlugin);
+    app.add_plugin(GameStatePlugin);
+    app.add_plugin(PageActivityPlugin);
+    app.add_plugin(NetworkDiagnosticsPlugin);
+    app.add_plugin(GameSnapshotPlugin);
+    app.add_plugin(ScoreboardPlugin);
+    app.add_plugin(SpectatorPlugin);
+    app.add_plugin(MovementValidationPlugin);
+    app.add_plugin(DeterministicSimulationPlugin);
+    #[cfg(target_arch = "wasm32")]
+    app.add_plugin(touch::TouchControlsPlugin);
     app.add_event::<PlayerMoved>();
+    app.add_event::<LaserDespawned>();
     //app.add_plugin(LogDiagnosticsPlugin::default());
-    //app.add_plugin(FrameTimeDiagnosticsPlugin::default());
     app.add_startup_system_to_stage(StartupStage::Startup, setup_system);
+    // local player systems, only while playing
     app.add_system_set(
         SystemSet::new()
-            .with_run_criteria(FixedTimestep::step(TIME_STEP as f64))
-            //.with_system(screen_print_text)
+            .with_run_criteria(capped_fixed_step.chain(local_player_running))
             .with_system(local_player_laser_shoot_system)
-            .with_system(laser_movable_system)
             .with_system(local_player_movement_system)
-            .with_system(on_local_player_moved_system)
+            .with_system(on_local_player_moved_system),
+    );
+    // world simulation, from the player registration until the disconnection
+    app.add_system_set(
+        SystemSet::new()
+            .with_run_criteria(capped_fixed_step.chain(world_running))
+            .with_system(laser_movable_system)
             .with_system(entities_from_blockchain_update_system)
             .with_system(interpolate_blockchain_players_state_system)
             .with_system(interpolate_blockchain_lasers_state_system)
-            .with_system(player_tag_animation_system)
+            .with_system(player_tag_system)
             .with_system(local_player_collectible_collision_system)
+            .with_system(pending_collectible_claims_timeout_system)
+            .with_system(power_up_system)
             .with_system(local_player_remote_enemy_lasers_collision_system) // our player getting hit by enemy lasers
             .with_system(remote_player_local_lasers_collision_system) // rendered remote player getting hit by my lasers
             .with_system(explosion_to_spawn_system)
             .with_system(explosion_animation_system),
     );
+    // every frame, the events only live for two frames
+    app.add_system(laser_despawned_system);
     app.run();
 }
 
-fn screen_print_text(time: Res<Time>) {
-    let current_time = time.seconds_since_startup();
-    let last_fps = 1.0 / time.delta_seconds_f64();
-    screen_print!(sec: 1, col: Color::CYAN, "fps: {last_fps:.0}");
-    //screen_print!(sec: 1, col: Color::GREEN, "current time: {current_time:.2}");
-}
-
 fn setup_system(
     mut commands: Commands,
     asset_server: Res<AssetServer>,
     mut texture_atlases: ResMut<Assets<TextureAtlas>>,
     mut windows: ResMut<Windows>,
+    input_bindings: Res<InputBindings>,
 ) {
     // 2D orthographic camera
     commands.spawn_bundle(Camera2dBundle::default());
@@ -145,7 +254,10 @@ fn setup_system(
 
     // load texture atlas and create a resource with Textures
     let background_texture = asset_server.load(BACKGROUND_SPRITE);
-    let collectible_texture = asset_server.load(COLLECTIBLE_SPRITE);
+    let collectible_sprites = CollectibleKind::ALL
+        .iter()
+        .map(|kind| (*kind, asset_server.load(kind.sprite())))
+        .collect::<HashMap<CollectibleKind, Handle<Image>>>();
     let laser_texture = asset_server.load(PLAYER_LASER_SPRITE);
     let player_sprites = PLAYER_SPRITES
         .iter()
@@ -153,16 +265,18 @@ fn setup_system(
         .collect::<HashMap<String, Handle<Image>>>();
 
     // create explosion texture atlas
-    let texture_handle = asset_server.load(EXPLOSION_SHEET);
-    let texture_atlas = TextureAtlas::from_grid(texture_handle, Vec2::new(64., 64.), 4, 4);
+    let explosion_sheet = asset_server.load(EXPLOSION_SHEET);
+    let texture_atlas = TextureAtlas::from_grid(explosion_sheet.clone(), Vec2::new(64., 64.), 4, 4);
     let explosion = texture_atlases.add(texture_atlas);
 
     let game_textures = GameTextures {
         player: player_sprites,
         laser: laser_texture,
-        collectible: collectible_texture.clone(),
+        collectible: collectible_sprites,
         background: background_texture.clone(),
         explosion,
+        explosion_sheet,
+        font: asset_server.load(FONT),
     };
     commands.insert_resource(game_textures);
 
@@ -170,6 +284,9 @@ fn setup_system(
     let game_state = RemoteGameState::default();
     commands.insert_resource(game_state);
 
+    // insert the chain coordinate space as a resource (adjusted by the contract screen events)
+    commands.insert_resource(ChainSpace::default());
+
     // add galaxy background
     commands.spawn_bundle(SpriteBundle {
         texture: background_texture,
@@ -177,34 +294,84 @@ fn setup_system(
     });
 
     // insert the on-screen text instructions
-    spawn_game_screen_instructions(&mut commands, &asset_server);
+    spawn_game_screen_instructions(
+        &mut commands,
+        &asset_server,
+        &instructions_text(&input_bindings),
+    );
 }
 
 fn entities_from_blockchain_update_system(
     time: Res<Time>,
     mut commands: Commands,
     mut game_state: ResMut<RemoteGameState>,
+    mut chain_space: ResMut<ChainSpace>,
     asset_server: Res<AssetServer>,
     game_textures: Res<GameTextures>,
+    mut remote_players_query: Query<(&mut Transform, &mut Velocity), With<RemotePlayer>>,
+    mut play_sound_events: EventWriter<PlaySound>,
+    mut network_metrics: ResMut<NetworkMetrics>,
+    mut movement_validation: ResMut<MovementValidation>,
 ) {
     GAME_ENTITY_UPDATE.with(|entities_update| {
-        let entities_update = entities_update.take();
-        for entity in entities_update.into_iter() {
-            let mapped_update = map_js_update_to_rust_entity_state(entity)
-                .map_err(|err| error!("Map error {:?}", err.to_string()))
-                .unwrap();
+        // a bounded batch per step, the rest stays queued for the next steps
+        let entities_update = {
+            let mut inbox = entities_update.borrow_mut();
+            network_metrics.queue_depth = inbox.len();
+            network_metrics.overflowed_updates = inbox.overflowed;
+            network_metrics.coalesced_updates = inbox.coalesced;
+            network_metrics.discarded_updates = inbox.discarded;
+            inbox.drain(MAX_UPDATES_PER_TICK)
+        };
+        let now_unix_millis = unix_time_millis();
+        let mut mapped_updates = entities_update
+            .into_iter()
+            .map(|entity| {
+                let operation = map_js_update_operation(&entity).unwrap_or_default();
+                let event_time = map_js_update_event_time(&entity);
+                let mapped_update = map_js_update_to_rust_entity_state(entity);
+                network_metrics.record_update(
+                    &operation,
+                    &mapped_update,
+                    event_time,
+                    time.seconds_since_startup(),
+                    now_unix_millis,
+                );
+                let mapped_update = mapped_update
+                    .map_err(|err| error!("Map error {:?}", err.to_string()))
+                    .ok()
+                    .flatten();
+                (mapped_update, event_time)
+            })
+            .collect::<VecDeque<(Option<RemoteStateType>, Option<f64>)>>();
+
+        while let Some((mapped_update, event_time)) = mapped_updates.pop_front() {
+            // drop the updates older than the ones already applied to their entity
+            if let Some(update) = mapped_update.as_ref() {
+                if !game_state.accept_update_sequence(update) {
+                    continue;
+                }
+            }
             match mapped_update {
                 Some(RemoteStateType::PlayerAdded(player_added)) => {
                     match player_added.r#type {
                         EntityType::Local => {
-                            // get texture for local player
+                            // get skin and texture for local player
+                            let skin = PlayerSkin::for_player(
+                                player_added.address.as_str(),
+                                player_added.skin.as_deref(),
+                            );
                             let player_texture =
-                                game_textures.player.get("local").cloned().unwrap();
+                                game_textures.player.get(skin.hull).cloned().unwrap();
 
                             // spawn the local player
                             let local_player_entity = commands
                                 .spawn_bundle(SpriteBundle {
                                     texture: player_texture,
+                                    sprite: Sprite {
+                                        color: skin.tint,
+                                        ..Default::default()
+                                    },
                                     transform: Transform {
                                         translation: player_added.position,
                                         rotation: player_added.rotation,
@@ -215,22 +382,25 @@ fn entities_from_blockchain_update_system(
                                 })
                                 .insert(LocalPlayer(player_added.uuid.clone()))
                                 .insert(SpriteSize::from(PLAYER_SIZE))
+                                .insert(Weapon::default())
+                                .insert(Health::new(PLAYER_MAX_HEALTH))
+                                .insert(ParticleEmitter::engine_trail())
                                 .insert(Velocity {
                                     linear: PLAYER_LINEAR_MOVEMENT_SPEED,
                                     rotational: f32::to_radians(PLAYER_LINEAR_ROTATION_SPEED),
+                                    vector: player_added.velocity,
+                                    ..Default::default()
                                 })
                                 .id();
 
-                            // spawn the text entity
+                            // spawn the name tag, attached to the ship
                             let text2d_entity = spawn_player_name_text2d_entity(
                                 &mut commands,
                                 &asset_server,
+                                local_player_entity,
                                 &player_added.name,
-                                &player_added.position,
+                                skin.tag_color(),
                             );
-                            commands
-                                .entity(text2d_entity)
-                                .insert(AnimateNameTranslation(local_player_entity));
 
                             // map local player uuid - entity id
                             game_state
@@ -239,6 +409,7 @@ fn entities_from_blockchain_update_system(
 
                             // add player tag to resources
                             game_state.add_new_player_tag(&player_added.uuid, text2d_entity);
+                            game_state.local_player = Some(player_added.clone());
                         }
                         EntityType::Remote => {
                             // add player to state and spawn new entity only if new player uuid
@@ -246,14 +417,22 @@ fn entities_from_blockchain_update_system(
                                 .add_new_remote_player(&player_added.uuid, player_added.clone())
                                 .is_none()
                             {
-                                // get texture for remote player
+                                // get skin and texture for remote player
+                                let skin = PlayerSkin::for_player(
+                                    player_added.address.as_str(),
+                                    player_added.skin.as_deref(),
+                                );
                                 let player_texture =
-                                    game_textures.player.get("remote").cloned().unwrap();
+                                    game_textures.player.get(skin.hull).cloned().unwrap();
 
                                 // spawn a new player entity
                                 let spawned_remote_player_entity = commands
                                     .spawn_bundle(SpriteBundle {
                                         texture: player_texture,
+                                        sprite: Sprite {
+                                            color: skin.tint,
+                                            ..Default::default()
+                                        },
                                         transform: Transform {
                                             translation: player_added.position,
                                             rotation: player_added.rotation,
@@ -264,22 +443,24 @@ fn entities_from_blockchain_update_system(
                                     })
                                     .insert(RemotePlayer(player_added.uuid.clone()))
                                     .insert(SpriteSize::from(PLAYER_SIZE))
+                                    .insert(Health::new(PLAYER_MAX_HEALTH))
+                                    .insert(ParticleEmitter::engine_trail())
                                     .insert(Velocity {
                                         linear: PLAYER_LINEAR_MOVEMENT_SPEED,
                                         rotational: f32::to_radians(PLAYER_LINEAR_ROTATION_SPEED),
+                                        vector: player_added.velocity,
+                                        ..Default::default()
                                     })
                                     .id();
 
-                                // spawn the text entity
+                                // spawn the name tag, attached to the ship
                                 let text2d_entity = spawn_player_name_text2d_entity(
                                     &mut commands,
                                     &asset_server,
+                                    spawned_remote_player_entity,
                                     &player_added.name,
-                                    &player_added.position,
+                                    skin.tag_color(),
                                 );
-                                commands
-                                    .entity(text2d_entity)
-                                    .insert(AnimateNameTranslation(spawned_remote_player_entity));
 
                                 // add player entity to resources
                                 game_state.add_new_remote_player_entity(
@@ -289,6 +470,9 @@ fn entities_from_blockchain_update_system(
 
                                 // add player tag to resources
                                 game_state.add_new_player_tag(&player_added.uuid, text2d_entity);
+
+                                play_sound_events
+                                    .send(PlaySound::stereo(SoundEffect::PlayerJoined));
                             }
                         }
                     }
@@ -298,16 +482,15 @@ fn entities_from_blockchain_update_system(
                     if let Some(entity_id) =
                         game_state.get_remote_player_entity(&player_to_remove.uuid)
                     {
-                        // despawn the remote player entity
-                        commands.entity(*entity_id).despawn();
+                        // despawn the remote player entity, along with its name tag
+                        commands.entity(*entity_id).despawn_recursive();
                     }
 
-                    // despawn tag entity id
-                    if let Some(entity_id) =
-                        game_state.get_player_tag_entity(&player_to_remove.uuid)
+                    if game_state
+                        .remote_players
+                        .contains_key(&player_to_remove.uuid)
                     {
-                        // despawn the remote player tag entity
-                        commands.entity(*entity_id).despawn();
+                        play_sound_events.send(PlaySound::stereo(SoundEffect::PlayerLeft));
                     }
 
                     // remove player from all collection states
@@ -315,11 +498,37 @@ fn entities_from_blockchain_update_system(
                 }
                 Some(RemoteStateType::PlayerMoved(player_moved)) => {
                     // check to see if the player has an entity id already (is registered). If not, skip update
-                    if let Some(_player) = game_state.remote_players.get(&player_moved.uuid) {
+                    if let Some(player) = game_state.remote_players.get(&player_moved.uuid) {
+                        // clamp or drop the implausible moves
+                        let player_moved = match validate_remote_move(
+                            &mut movement_validation,
+                            player,
+                            player_moved,
+                            time.seconds_since_startup(),
+                            event_time,
+                        ) {
+                            Some(player_moved) => player_moved,
+                            None => continue,
+                        };
+
                         // update the inner state
                         game_state
                             .remote_players
                             .insert(player_moved.uuid.clone(), player_moved.clone());
+
+                        // restart the extrapolation from the chain state
+                        if let Some(entity_id) =
+                            game_state.get_remote_player_entity(&player_moved.uuid)
+                        {
+                            if let Ok((mut transform, mut velocity)) =
+                                remote_players_query.get_mut(*entity_id)
+                            {
+                                transform.translation.x = player_moved.position.x;
+                                transform.translation.y = player_moved.position.y;
+                                transform.rotation = player_moved.rotation;
+                                velocity.vector = player_moved.velocity;
+                            }
+                        }
                     }
                 }
                 Some(RemoteStateType::TokenAdded(token_added)) => {
@@ -327,7 +536,11 @@ fn entities_from_blockchain_update_system(
                     game_state.add_new_collectible(&token_added.uuid, token_added.clone());
                     let entity_id = spawn_collectible_closure(
                         &mut commands,
-                        game_textures.collectible.clone(),
+                        game_textures
+                            .collectible
+                            .get(&token_added.kind)
+                            .cloned()
+                            .unwrap_or_default(),
                         token_added.clone(),
                     );
                     // add token entity
@@ -342,8 +555,37 @@ fn entities_from_blockchain_update_system(
                         game_state.remove_collectible(&uuid);
                     }
                 }
-                Some(RemoteStateType::TokenCollected(CollectedEntity { uuid, .. })) => {
+                Some(RemoteStateType::TokenCollected(CollectedEntity {
+                    uuid,
+                    player_uuid,
+                    value,
+                    ..
+                })) => {
+                    *game_state
+                        .player_scores
+                        .entry(player_uuid.clone())
+                        .or_insert(0.0) += value;
                     //info!("TOKEN COLLECTED {:?}", uuid);
+                    // confirm (or correct) the local prediction, if any
+                    if let Some(claim) = game_state.take_pending_collectible_claim(&uuid) {
+                        if !claim.player_uuid.eq(&player_uuid) {
+                            info!(
+                                "Token {:?} predicted for player {:?} but collected by {:?}",
+                                uuid, claim.player_uuid, player_uuid
+                            );
+                        }
+                    }
+                    // the collection is confirmed: power-ups take effect on the collecting ship
+                    let kind = game_state
+                        .remote_collectibles
+                        .get(&uuid)
+                        .map(|collectible| collectible.kind)
+                        .unwrap_or_default();
+                    if kind.is_power_up() {
+                        if let Some(player_entity) = game_state.entity_players.get(&player_uuid) {
+                            commands.entity(*player_entity).insert(PowerUp::new(kind));
+                        }
+                    }
                     // despawn entity id
                     if let Some(entity_id) = game_state.get_collectible_entity(&uuid) {
                         // despawn the remote collectible entity
@@ -352,68 +594,33 @@ fn entities_from_blockchain_update_system(
                         game_state.remove_collectible(&uuid);
                     }
                 }
-                Some(RemoteStateType::LasersShot((player_uuid, lasers_shot))) => {
+                Some(RemoteStateType::LasersShot((player_uuid, _, lasers_shot))) => {
                     //info!("[BEVY] LASERS SHOT {:?}", &lasers_shot);
 
                     // get current in-memory player lasers map
-                    let mut tree_map: BTreeMap<String, RemoteLaserState> = BTreeMap::new();
                     let mut player_lasers_map = game_state
                         .remote_lasers
                         .get(&player_uuid)
-                        .unwrap_or_else(|| &mut tree_map)
-                        .clone();
+                        .cloned()
+                        .unwrap_or_default();
 
                     // 3 options:
                     // - overwrite an existing state
                     // - delete an entry not in the update
                     // - a new laser entry
-
-                    let mut laser_shot_uuids = lasers_shot
-                        .iter()
-                        .cloned()
-                        .map(|shot| shot.uuid)
-                        .collect::<HashSet<String>>();
-                    //info!("---------------------");
-                    //info!("LASERS UPDATE (BEFORE INTERSEC) {:?}", &laser_shot_uuids);
-
-                    let mut game_lasers_uuids = player_lasers_map
-                        .keys()
-                        .cloned()
-                        .collect::<HashSet<String>>();
-                    //info!(
-                    //    "GAME LASERS (BEFORE INTERSEC) {:?}",
-                    //    &game_lasers_uuids
-                    //);
-
-                    // -- still persisting lasers. Update values in the states map
-                    let persisting_laser_uuids =
-                        inplace_intersection(&mut laser_shot_uuids, &mut game_lasers_uuids);
-                    /*
-                    info!(
-                        "PERSISTING LASERS (AFTER INTERSEC)  {:?}",
-                        &persisting_laser_uuids
-                    );
-                    info!(
-                        "NEW LASERS (AFTER INTERSEC)  {:?}",
-                        &laser_shot_uuids
-                    );
-                    info!(
-                        "LASERS TO BE REMOVED (AFTER INTERSEC)  {:?}",
-                        &game_lasers_uuids
+                    let lasers_diff = keyed_diff(
+                        &player_lasers_map,
+                        lasers_shot,
+                        |laser| laser.uuid.clone(),
+                        |_, _| true,
                     );
-                    */
 
-                    for persisting_laser_uuid in persisting_laser_uuids.iter() {
+                    // -- still persisting lasers. Update values in the states map
+                    for laser_shot_new_state in lasers_diff.changed.into_iter() {
                         //info!("@@ UPDATING PERSISTING LASER");
-                        // get the new recurring state from the sent update
-                        let laser_shot_new_state = lasers_shot
-                            .iter()
-                            .find(|laser| laser.uuid.eq(persisting_laser_uuid))
-                            .cloned()
-                            .unwrap();
                         // replace the recurring laser in the internal state
                         player_lasers_map.insert(
-                            persisting_laser_uuid.clone(),
+                            laser_shot_new_state.uuid.clone(),
                             RemoteLaserState {
                                 player_uuid: laser_shot_new_state.player_uuid,
                                 uuid: laser_shot_new_state.uuid,
@@ -421,30 +628,45 @@ fn entities_from_blockchain_update_system(
                                 y: laser_shot_new_state.y,
                                 rot: laser_shot_new_state.rot,
                                 w: laser_shot_new_state.w,
+                                weapon: laser_shot_new_state.weapon,
                             },
                         );
                     }
 
-                    // -- laser_shot_uuids must now have the reduced states => only new lasers, create them
-                    for laser_shot_uuid in laser_shot_uuids.iter() {
+                    // -- only new lasers, create them
+                    let mut laser_sound_played = false;
+                    for laser_shot_new_state in lasers_diff.added.into_iter() {
                         //info!("@@ CREATING NEW LASER");
-                        // get the new laser state from the sent update
-                        let laser_shot_new_state = lasers_shot
-                            .iter()
-                            .find(|laser| laser.uuid.eq(laser_shot_uuid))
-                            .cloned()
-                            .unwrap();
-
                         // spawn the new laser
-                        let new_laser_entity_id = spawn_laser_closure(
+                        let new_laser_entity_id = match spawn_laser_closure(
                             &mut commands,
                             game_textures.laser.clone(),
                             laser_shot_new_state.clone(),
-                        );
+                            time.seconds_since_startup(),
+                        ) {
+                            Some(entity) => entity,
+                            None => {
+                                warn!("Invalid laser uuid {:?}", laser_shot_new_state.uuid);
+                                continue;
+                            }
+                        };
+
+                        // one firing sound per update, however many lasers were shot
+                        if !laser_sound_played {
+                            laser_sound_played = true;
+                            play_sound_events.send(PlaySound::at(
+                                SoundEffect::LaserFire,
+                                Vec3::new(
+                                    laser_shot_new_state.x as f32,
+                                    laser_shot_new_state.y as f32,
+                                    0.,
+                                ),
+                            ));
+                        }
 
                         // add the new laser to the internal state
                         player_lasers_map.insert(
-                            laser_shot_uuid.clone(),
+                            laser_shot_new_state.uuid.clone(),
                             RemoteLaserState {
                                 player_uuid: laser_shot_new_state.player_uuid,
                                 uuid: laser_shot_new_state.uuid,
@@ -452,6 +674,7 @@ fn entities_from_blockchain_update_system(
                                 y: laser_shot_new_state.y,
                                 rot: laser_shot_new_state.rot,
                                 w: laser_shot_new_state.w,
+                                weapon: laser_shot_new_state.weapon,
                             },
                         );
 
@@ -466,21 +689,13 @@ fn entities_from_blockchain_update_system(
                         */
                     }
 
-                    // -- game_lasers_uuids must now have the reduced states => old lasers to be deleted
-                    for laser_to_remove_uuid in game_lasers_uuids.iter() {
+                    // -- old lasers missing from the update, to be deleted
+                    for laser_to_remove_uuid in lasers_diff.removed.iter() {
                         //info!("@@ REMOVING LASER");
                         // remove laser from the states map
                         player_lasers_map.remove(laser_to_remove_uuid);
 
                         // TODO: despawn the laser entity
-                        /*
-                        game_state
-                            .entity_lasers
-                            .get_mut(&player_uuid)
-                            .and_then(|entities_set| {
-                                Some(entities_set.insert(new_laser_entity_id))
-                            });
-                        */
                     }
 
                     game_state
@@ -488,131 +703,207 @@ fn entities_from_blockchain_update_system(
                         .insert(player_uuid, player_lasers_map);
                     //info!("---------------------");
                 }
+                Some(RemoteStateType::ScreenWidthAdjusted(width)) => {
+                    chain_space.width = width;
+                }
+                Some(RemoteStateType::ScreenHeightAdjusted(height)) => {
+                    chain_space.height = height;
+                }
+                Some(RemoteStateType::PlayersSnapshot(players)) => {
+                    // applied right away, before the rest of the batch
+                    let updates = reconcile_players(&game_state, players);
+                    for update in updates.into_iter().rev() {
+                        mapped_updates.push_front((Some(update), None));
+                    }
+                }
+                Some(RemoteStateType::CollectiblesSnapshot(collectibles)) => {
+                    let updates = reconcile_collectibles(&game_state, collectibles);
+                    for update in updates.into_iter().rev() {
+                        mapped_updates.push_front((Some(update), None));
+                    }
+                }
                 None => {}
             }
         }
+        network_metrics.stale_updates = game_state.stale_updates;
     });
 }
 
 fn interpolate_blockchain_lasers_state_system(
     mut commands: Commands,
+    time: Res<Time>,
     win_size: Res<WinSize>,
-    mut game_state: ResMut<RemoteGameState>,
-    mut query: Query<(Entity, &mut Transform, &Velocity, &RemoteLaser), With<RemoteLaser>>,
+    game_state: Res<RemoteGameState>,
+    mut query: Query<
+        (
+            Entity,
+            &mut Transform,
+            &Velocity,
+            &Movable,
+            &RemoteLaser,
+            Option<&mut FixedLaser>,
+        ),
+        With<RemoteLaser>,
+    >,
+    mut laser_despawned_events: EventWriter<LaserDespawned>,
 ) {
-    for (entity, mut transform, velocity, remote_laser) in query.iter_mut() {
+    let now = time.seconds_since_startup();
+    for (entity, mut transform, velocity, movable, remote_laser, fixed_laser) in query.iter_mut() {
         let LaserData {
-            uuid,
-            player_uuid,
-            start_pos,
-            start_rot,
+            uuid, player_uuid, ..
         } = &remote_laser.0;
 
-        if let Some(remote_laser_state) = game_state.remote_lasers.get_mut(player_uuid) {
-            let remote_laser_pos = remote_laser_state.get(&uuid.to_string());
-            if let Some(remote_laser_pos) = remote_laser_pos {
-                // TODO: how to interpolate - use the blockchain x, y state too or just the rotation ?
-                transform.rotation = Quat::from_array([
-                    0.,
-                    0.,
-                    remote_laser_pos.rot as f32,
-                    remote_laser_pos.w as f32,
-                ]);
+        // the rotation follows the chain state while the laser has one
+        let remote_laser_pos = game_state
+            .remote_lasers
+            .get(player_uuid)
+            .and_then(|lasers| lasers.get(&uuid.to_string()));
+        if let Some(remote_laser_pos) = remote_laser_pos {
+            // TODO: how to interpolate - use the blockchain x, y state too or just the rotation ?
+            transform.rotation = Quat::from_array([
+                0.,
+                0.,
+                remote_laser_pos.rot as f32,
+                remote_laser_pos.w as f32,
+            ]);
+        }
+        match fixed_laser {
+            Some(mut fixed_laser) => {
+                step_fixed_laser(&mut fixed_laser, Fixed::from_f32(TIME_STEP));
+                fixed_laser.write_float(&mut transform);
+            }
+            None => {
                 let movement_direction = transform.rotation * Vec3::Y;
                 transform.translation += movement_direction * velocity.linear * TIME_STEP;
-
-                // despawn when out of screen
-                let mut should_despawn = false;
-
-                if transform.translation.y > win_size.h / 2.
-                    || transform.translation.y < -win_size.h / 2.
-                    || transform.translation.x > win_size.w / 2.
-                    || transform.translation.x < -win_size.w / 2.
-                {
-                    should_despawn = true;
-                    commands.entity(entity).despawn();
-
-                    // when out of screen remove remote laser entity if present
-                    game_state
-                        .entity_lasers
-                        .get_mut(player_uuid)
-                        .and_then(|entities_set| Some(entities_set.remove(&entity)));
-                }
             }
         }
+
+        // despawn past its range or lifetime, or when out of screen, also once its chain state
+        // was dropped (the lifetime bounds it)
+        let bounds = Vec2::new(win_size.w, win_size.h);
+        let reason =
+            movable.laser_despawn_reason(&remote_laser.0, transform.translation, bounds, now);
+        if let Some(reason) = reason {
+            commands.entity(entity).despawn();
+            laser_despawned_events.send(LaserDespawned {
+                entity,
+                uuid: *uuid,
+                player_uuid: player_uuid.clone(),
+                reason,
+            });
+        }
     }
 }
 
 fn interpolate_blockchain_players_state_system(
     mut commands: Commands,
     mut game_state: ResMut<RemoteGameState>,
-    mut query: Query<(Entity, &mut Transform, &Velocity, &RemotePlayer), With<RemotePlayer>>,
+    mut query: Query<
+        (
+            Entity,
+            &mut Transform,
+            &mut Velocity,
+            &RemotePlayer,
+            Option<&PowerUp>,
+        ),
+        With<RemotePlayer>,
+    >,
 ) {
-    for (entity, mut transform, velocity, remote_player) in query.iter_mut() {
+    for (entity, mut transform, mut velocity, remote_player, power_up) in query.iter_mut() {
         if let Some(player_updated_state) = game_state.remote_players.get_mut(&remote_player.0) {
-            // TODO: how to interpolate - use the blockchain x, y state too or just the rotation ?
+            // the remote inputs are unknown: extrapolate with the chain velocity, drag and bounds
             transform.rotation = player_updated_state.rotation;
-            let movement_direction = transform.rotation * Vec3::Y;
-            transform.translation += movement_direction * velocity.linear * TIME_STEP;
-
-            // apply bounds to movement
-            let extents = Vec3::from((BOUNDS / 2.0, 0.0));
-            transform.translation = transform.translation.clamp(-extents, extents);
+            let controls = ShipControls {
+                speed_boost: is_power_up_active(power_up, CollectibleKind::SpeedBoost),
+                ..Default::default()
+            };
+            step_ship(&mut transform, &mut velocity, &controls, TIME_STEP);
         }
     }
 }
 
 fn local_player_collectible_collision_system(
     mut commands: Commands,
+    time: Res<Time>,
+    chain_space: Res<ChainSpace>,
     mut game_state: ResMut<RemoteGameState>,
-    collectibles_query: Query<(Entity, &Transform, &SpriteSize, &Collectible), With<Collectible>>,
+    mut collectibles_query: Query<
+        (Entity, &Transform, &Collectible, &mut Visibility),
+        With<Collectible>,
+    >,
     players_query: Query<
-        (Entity, &Transform, &SpriteSize),
+        (
+            Entity,
+            &Transform,
+            Option<&LocalPlayer>,
+            Option<&RemotePlayer>,
+        ),
         (Or<(With<LocalPlayer>, With<RemotePlayer>)>),
     >,
+    mut play_sound_events: EventWriter<PlaySound>,
 ) {
-    let mut despawned_entities: HashSet<Entity> = HashSet::new();
-
     // iterate through the collectibles
-    for (collectible_entity, collectible_tf, collectible_size, collectible_id) in
-        collectibles_query.iter()
+    for (collectible_entity, collectible_tf, collectible_id, mut visibility) in
+        collectibles_query.iter_mut()
     {
-        if despawned_entities.contains(&collectible_entity) {
+        // skip tokens already awaiting a chain confirmation
+        if game_state.has_pending_collectible_claim(&collectible_id.0) {
             continue;
         }
 
-        let collectible_scale = Vec2::from(collectible_tf.scale.xy());
-
         // iterate through the players
-        for (player_entity, player_tf, player_size) in players_query.iter() {
-            if despawned_entities.contains(&player_entity)
-                || despawned_entities.contains(&collectible_entity)
-            {
-                continue;
-            }
-            let player_scale = Vec2::from(player_tf.scale.xy());
-
-            // determine if collision
-            let collision = collide(
-                collectible_tf.translation,
-                collectible_size.0 * collectible_scale,
-                player_tf.translation,
-                player_size.0 * player_scale,
-            );
+        for (player_entity, player_tf, local_player, remote_player) in players_query.iter() {
+            // determine if collision, using the same geometry as the contract
+            let collision =
+                chain_space.is_token_collected(player_tf.translation, collectible_tf.translation);
 
             // perform collision
-            if let Some(_) = collision {
+            if collision {
                 //info!("COLLISION: Entity UUID {:?}", &collectible_id.0);
-                // remove the collectible
-                commands.entity(collectible_entity).despawn();
-                despawned_entities.insert(collectible_entity);
-                // remove token from all collection states
-                game_state.remove_collectible(&collectible_id.0);
-
-                // spawn the explosionToSpawn
-                commands
-                    .spawn()
-                    .insert(ExplosionToSpawn(collectible_tf.translation.clone()));
+                let player_uuid = local_player
+                    .map(|player| player.0.clone())
+                    .or_else(|| remote_player.map(|player| player.0.clone()))
+                    .unwrap_or_default();
+
+                // hide the collectible until the chain confirms (or rejects) the collection
+                visibility.is_visible = false;
+                game_state.add_pending_collectible_claim(
+                    &collectible_id.0,
+                    PendingCollectibleClaim {
+                        player_uuid,
+                        claimed_at: time.seconds_since_startup(),
+                    },
+                );
+
+                spawn_particle_burst(
+                    &mut commands,
+                    ParticleEffect::PickupBurst,
+                    collectible_tf.translation,
+                );
+                play_sound_events.send(PlaySound::at(
+                    SoundEffect::TokenPickup,
+                    collectible_tf.translation,
+                ));
+
+                break;
+            }
+        }
+    }
+}
+
+fn pending_collectible_claims_timeout_system(
+    time: Res<Time>,
+    mut game_state: ResMut<RemoteGameState>,
+    mut collectibles_query: Query<&mut Visibility, With<Collectible>>,
+) {
+    let expired_claims = game_state
+        .take_expired_collectible_claims(time.seconds_since_startup(), COLLECTIBLE_CLAIM_TIMEOUT);
+
+    // the chain never confirmed the collection, restore the tokens
+    for uuid in expired_claims.iter() {
+        if let Some(entity_id) = game_state.get_collectible_entity(uuid) {
+            if let Ok(mut visibility) = collectibles_query.get_mut(*entity_id) {
+                visibility.is_visible = true;
             }
         }
     }
@@ -622,7 +913,19 @@ fn local_player_remote_enemy_lasers_collision_system(
     mut commands: Commands,
     mut game_state: ResMut<RemoteGameState>,
     lasers_query: Query<(Entity, &Transform, &SpriteSize, &RemoteLaser), With<RemoteLaser>>,
-    players_query: Query<(Entity, &Transform, &SpriteSize, &LocalPlayer), (With<LocalPlayer>)>,
+    mut players_query: Query<
+        (
+            Entity,
+            &Transform,
+            &SpriteSize,
+            &mut Health,
+            Option<&PowerUp>,
+            &LocalPlayer,
+        ),
+        (With<LocalPlayer>),
+    >,
+    mut play_sound_events: EventWriter<PlaySound>,
+    mut laser_despawned_events: EventWriter<LaserDespawned>,
 ) {
     let mut despawned_entities: HashSet<Entity> = HashSet::new();
 
@@ -635,7 +938,9 @@ fn local_player_remote_enemy_lasers_collision_system(
         let laser_scale = Vec2::from(laser_tf.scale.xy());
 
         // iterate through the players
-        for (player_entity, player_tf, player_size, local_player) in players_query.iter() {
+        for (player_entity, player_tf, player_size, mut health, power_up, local_player) in
+            players_query.iter_mut()
+        {
             if despawned_entities.contains(&player_entity)
                 || despawned_entities.contains(&laser_entity)
             {
@@ -656,17 +961,33 @@ fn local_player_remote_enemy_lasers_collision_system(
                 // remove the laser
                 commands.entity(laser_entity).despawn();
                 despawned_entities.insert(laser_entity);
-
-                // remote the hit player
-                let hit_player = game_state.entity_players.get(&local_player.0);
-                if let Some(hit_entity) = hit_player {
-                    commands.entity(*hit_entity).despawn();
+                laser_despawned_events.send(LaserDespawned {
+                    entity: laser_entity,
+                    uuid: laser_id.0.uuid,
+                    player_uuid: laser_id.0.player_uuid.clone(),
+                    reason: LaserDespawnReason::Hit,
+                });
+
+                // damage the hit player (unless shielded), remove it once its health is depleted
+                let shielded = is_power_up_active(power_up, CollectibleKind::Shield);
+                if !shielded && health.damage(laser_id.0.weapon.damage()) {
+                    let hit_player = game_state.entity_players.get(&local_player.0);
+                    if let Some(hit_entity) = hit_player {
+                        commands.entity(*hit_entity).despawn_recursive();
+                        despawned_entities.insert(*hit_entity);
+                    }
+                    // only a destroyed ship explodes, a hit just sparks
+                    commands
+                        .spawn()
+                        .insert(ExplosionToSpawn(player_tf.translation.clone()));
                 }
 
-                // spawn the explosionToSpawn
-                commands
-                    .spawn()
-                    .insert(ExplosionToSpawn(laser_tf.translation.clone()));
+                spawn_particle_burst(
+                    &mut commands,
+                    ParticleEffect::ImpactSparks,
+                    laser_tf.translation,
+                );
+                play_sound_events.send(PlaySound::at(SoundEffect::Hit, laser_tf.translation));
 
                 break;
             }
@@ -678,7 +999,19 @@ fn remote_player_local_lasers_collision_system(
     mut commands: Commands,
     mut game_state: ResMut<RemoteGameState>,
     lasers_query: Query<(Entity, &Transform, &SpriteSize, &LocalLaser), With<LocalLaser>>,
-    players_query: Query<(Entity, &Transform, &SpriteSize, &RemotePlayer), (With<RemotePlayer>)>,
+    mut players_query: Query<
+        (
+            Entity,
+            &Transform,
+            &SpriteSize,
+            &mut Health,
+            Option<&PowerUp>,
+            &RemotePlayer,
+        ),
+        (With<RemotePlayer>),
+    >,
+    mut play_sound_events: EventWriter<PlaySound>,
+    mut laser_despawned_events: EventWriter<LaserDespawned>,
 ) {
     let mut despawned_entities: HashSet<Entity> = HashSet::new();
 
@@ -691,7 +1024,9 @@ fn remote_player_local_lasers_collision_system(
         let laser_scale = Vec2::from(laser_tf.scale.xy());
 
         // iterate through the players
-        for (player_entity, player_tf, player_size, local_player) in players_query.iter() {
+        for (player_entity, player_tf, player_size, mut health, power_up, local_player) in
+            players_query.iter_mut()
+        {
             if despawned_entities.contains(&player_entity)
                 || despawned_entities.contains(&laser_entity)
             {
@@ -712,17 +1047,33 @@ fn remote_player_local_lasers_collision_system(
                 // remove the laser
                 commands.entity(laser_entity).despawn();
                 despawned_entities.insert(laser_entity);
-
-                // remote the hit player
-                let hit_player = game_state.entity_players.get(&local_player.0);
-                if let Some(hit_entity) = hit_player {
-                    commands.entity(*hit_entity).despawn();
+                laser_despawned_events.send(LaserDespawned {
+                    entity: laser_entity,
+                    uuid: laser_id.0.uuid,
+                    player_uuid: laser_id.0.player_uuid.clone(),
+                    reason: LaserDespawnReason::Hit,
+                });
+
+                // damage the hit player (unless shielded), remove it once its health is depleted
+                let shielded = is_power_up_active(power_up, CollectibleKind::Shield);
+                if !shielded && health.damage(laser_id.0.weapon.damage()) {
+                    let hit_player = game_state.entity_players.get(&local_player.0);
+                    if let Some(hit_entity) = hit_player {
+                        commands.entity(*hit_entity).despawn_recursive();
+                        despawned_entities.insert(*hit_entity);
+                    }
+                    // only a destroyed ship explodes, a hit just sparks
+                    commands
+                        .spawn()
+                        .insert(ExplosionToSpawn(player_tf.translation.clone()));
                 }
 
-                // spawn the explosionToSpawn
-                commands
-                    .spawn()
-                    .insert(ExplosionToSpawn(laser_tf.translation.clone()));
+                spawn_particle_burst(
+                    &mut commands,
+                    ParticleEffect::ImpactSparks,
+                    laser_tf.translation,
+                );
+                play_sound_events.send(PlaySound::at(SoundEffect::Hit, laser_tf.translation));
 
                 break;
             }
@@ -736,36 +1087,40 @@ fn local_player_movement_system(
     game_textures: Res<GameTextures>,
     mut game_state: ResMut<RemoteGameState>,
     mut player_moved_events: EventWriter<PlayerMoved>,
-    keyboard_input: Res<Input<KeyCode>>,
-    mut query: Query<(&Velocity, &mut Transform, &LocalPlayer), With<LocalPlayer>>,
+    input_actions: Res<InputActions>,
+    mut query: Query<
+        (
+            &mut Velocity,
+            &mut Transform,
+            &LocalPlayer,
+            Option<&PowerUp>,
+            Option<&mut FixedShip>,
+        ),
+        With<LocalPlayer>,
+    >,
 ) {
-    for (velocity, mut transform, local_player) in query.iter_mut() {
-        // ship rotation
-        let mut rotation_factor = 0.0;
-
-        if keyboard_input.pressed(KeyCode::Left) {
-            rotation_factor += 1.0;
-        }
-
-        if keyboard_input.pressed(KeyCode::Right) {
-            rotation_factor -= 1.0;
+    for (mut velocity, mut transform, local_player, power_up, fixed_ship) in query.iter_mut() {
+        let controls = ShipControls {
+            rotation: input_actions.rotation,
+            thrust: input_actions.thrust,
+            brake: input_actions.brake,
+            boost: input_actions.boost,
+            speed_boost: is_power_up_active(power_up, CollectibleKind::SpeedBoost),
+        };
+        match fixed_ship {
+            // deterministic simulation, the transform only renders the fixed state
+            Some(mut fixed_ship) => {
+                step_fixed_ship(&mut fixed_ship, &controls, Fixed::from_f32(TIME_STEP));
+                fixed_ship.write_float(&mut transform, &mut velocity);
+            }
+            None => step_ship(&mut transform, &mut velocity, &controls, TIME_STEP),
         }
 
-        let rotation_delta =
-            Quat::from_rotation_z(rotation_factor * velocity.rotational * TIME_STEP);
-        transform.rotation *= rotation_delta;
-
-        let movement_direction = transform.rotation * Vec3::Y;
-        transform.translation += movement_direction * velocity.linear * TIME_STEP;
-
-        // limit the movement within the screen
-        let extents = Vec3::from((BOUNDS / 2.0, 0.0));
-        transform.translation = transform.translation.clamp(-extents, extents);
-
         // send message about player translation
         player_moved_events.send(PlayerMoved {
             pos: transform.translation,
             rot: transform.rotation,
+            vel: velocity.vector,
         });
     }
 }
@@ -774,8 +1129,11 @@ fn on_local_player_moved_system(mut events: EventReader<PlayerMoved>) {
     for movement_event in events.iter() {
         // on each player move push the new position to js over the wasm-bounded thread
         LOCAL_PLAYER_POSITION.with(|pos| {
-            pos.borrow_mut()
-                .from_game_vector(movement_event.pos, movement_event.rot);
+            pos.borrow_mut().from_game_vector(
+                movement_event.pos,
+                movement_event.rot,
+                movement_event.vel,
+            );
         });
     }
 }
@@ -786,14 +1144,45 @@ fn local_player_laser_shoot_system(
     game_textures: Res<GameTextures>,
     mut game_state: ResMut<RemoteGameState>,
     mut player_moved_events: EventWriter<PlayerMoved>,
-    keyboard_input: Res<Input<KeyCode>>,
-    mut query: Query<(&Velocity, &mut Transform, &LocalPlayer), With<LocalPlayer>>,
+    mut input_actions: ResMut<InputActions>,
+    mut query: Query<
+        (
+            &mut Weapon,
+            &mut Transform,
+            &LocalPlayer,
+            Option<&PowerUp>,
+            Option<&FixedShip>,
+        ),
+        With<LocalPlayer>,
+    >,
+    mut play_sound_events: EventWriter<PlaySound>,
 ) {
-    for (velocity, mut transform, local_player) in query.iter_mut() {
-        // if space is pressed, shoot laser
-        if keyboard_input.just_pressed(KeyCode::Space) {
+    // consume the pending shot and weapon switch requests
+    let fire = std::mem::take(&mut input_actions.fire);
+    let switch_weapon = std::mem::take(&mut input_actions.switch_weapon);
+
+    for (mut weapon, mut transform, local_player, power_up, fixed_ship) in query.iter_mut() {
+        // rapid fire cools the weapon down faster
+        if is_power_up_active(power_up, CollectibleKind::RapidFire) {
+            weapon.tick(TIME_STEP * POWER_UP_RAPID_FIRE_FACTOR);
+        } else {
+            weapon.tick(TIME_STEP);
+        }
+        if switch_weapon {
+            weapon.switch();
+        }
+
+        // shoot when the weapon triggers (cooldown, heat and charge permitting)
+        if !weapon.trigger(fire, input_actions.fire_held, TIME_STEP) {
+            continue;
+        }
+        play_sound_events.send(PlaySound::at(SoundEffect::LaserFire, transform.translation));
+
+        let kind = weapon.kind;
+        for spread_angle in kind.spread_angles() {
             let uuid = Uuid::new_v4();
             let laser_texture = game_textures.laser.clone();
+            let laser_rotation = transform.rotation * Quat::from_rotation_z(*spread_angle);
             let laser_entity_id = commands
                 .spawn_bundle(SpriteBundle {
                     texture: laser_texture,
@@ -803,8 +1192,8 @@ fn local_player_laser_shoot_system(
                             transform.translation.y,
                             0.,
                         ),
-                        rotation: transform.rotation.clone(),
-                        scale: Vec3::new(SPRITE_SCALE, SPRITE_SCALE, 1.),
+                        rotation: laser_rotation,
+                        scale: Vec3::new(kind.projectile_scale(), kind.projectile_scale(), 1.),
                         ..Default::default()
                     },
                     ..Default::default()
@@ -813,15 +1202,26 @@ fn local_player_laser_shoot_system(
                     uuid: uuid.clone(),
                     player_uuid: local_player.0.clone(),
                     start_pos: transform.translation.clone(),
-                    start_rot: transform.rotation.clone(),
+                    start_rot: laser_rotation,
+                    weapon: kind,
+                    spawned_at: time.seconds_since_startup(),
                 }))
                 .insert(SpriteSize::from(PLAYER_LASER_SIZE))
                 .insert(Movable { auto_despawn: true })
                 .insert(Velocity {
-                    linear: LASER_LINEAR_MOVEMENT_SPEED,
+                    linear: kind.projectile_speed(),
                     rotational: f32::to_radians(0.0),
+                    ..Default::default()
                 })
                 .id();
+            // shot from the fixed state of the ship, rather than from its rendered transform
+            if let Some(fixed_ship) = fixed_ship {
+                commands.entity(laser_entity_id).insert(FixedLaser::shot_by(
+                    fixed_ship,
+                    *spread_angle,
+                    kind.projectile_speed(),
+                ));
+            }
 
             // insert the laser entity
             if let Some(entities_set) = game_state.entity_lasers.get_mut(&local_player.0) {
@@ -837,44 +1237,76 @@ fn local_player_laser_shoot_system(
     }
 }
 
+/// Forgets the despawned lasers, whatever despawned them
+fn laser_despawned_system(
+    mut game_state: ResMut<RemoteGameState>,
+    mut laser_despawned_events: EventReader<LaserDespawned>,
+) {
+    for despawned in laser_despawned_events.iter() {
+        if let Some(entities_set) = game_state.entity_lasers.get_mut(&despawned.player_uuid) {
+            entities_set.remove(&despawned.entity);
+        }
+    }
+}
+
 fn laser_movable_system(
     mut commands: Commands,
+    time: Res<Time>,
     win_size: Res<WinSize>,
-    mut game_state: ResMut<RemoteGameState>,
-    mut query: Query<(Entity, &Velocity, &mut Transform, &Movable, &LocalLaser), With<LocalLaser>>,
+    mut query: Query<
+        (
+            Entity,
+            &Velocity,
+            &mut Transform,
+            &Movable,
+            &LocalLaser,
+            Option<&mut FixedLaser>,
+        ),
+        With<LocalLaser>,
+    >,
+    mut laser_despawned_events: EventWriter<LaserDespawned>,
 ) {
+    let now = time.seconds_since_startup();
     let mut serialized_lasers_data: Vec<String> = vec![];
-    for (entity, velocity, mut transform, movable, local_laser) in query.iter_mut() {
+    for (entity, velocity, mut transform, movable, local_laser, fixed_laser) in query.iter_mut() {
         let LaserData {
             uuid,
             player_uuid,
             start_pos,
             start_rot,
+            weapon,
+            spawned_at,
         } = &local_laser.0;
 
         // get the laser angle at which it was shot at (it is CONSTANT)
         transform.rotation = start_rot.clone();
 
         // extrapolate the position
-        let movement_direction = transform.rotation * Vec3::Y;
-        transform.translation += movement_direction * velocity.linear * TIME_STEP;
+        match fixed_laser {
+            Some(mut fixed_laser) => {
+                step_fixed_laser(&mut fixed_laser, Fixed::from_f32(TIME_STEP));
+                fixed_laser.write_float(&mut transform);
+            }
+            None => {
+                let movement_direction = transform.rotation * Vec3::Y;
+                transform.translation += movement_direction * velocity.linear * TIME_STEP;
+            }
+        }
 
-        // despawn when out of screen
+        // despawn past its range or lifetime, or when out of screen
+        let bounds = Vec2::new(win_size.w, win_size.h);
+        let reason =
+            movable.laser_despawn_reason(&local_laser.0, transform.translation, bounds, now);
         let mut should_despawn = false;
-
-        if transform.translation.y > win_size.h / 2.
-            || transform.translation.y < -win_size.h / 2.
-            || transform.translation.x > win_size.w / 2.
-            || transform.translation.x < -win_size.w / 2.
-        {
+        if let Some(reason) = reason {
             should_despawn = true;
             commands.entity(entity).despawn();
-
-            // remove laser entity if present
-            game_state
-                .entity_lasers
-                .get_mut(player_uuid)
-                .and_then(|entities_set| Some(entities_set.remove(&entity)));
+            laser_despawned_events.send(LaserDespawned {
+                entity,
+                uuid: *uuid,
+                player_uuid: player_uuid.clone(),
+                reason,
+            });
         }
 
         // any entity that is not to be despawned, is to be serialized and added to the output
@@ -886,6 +1318,11 @@ fn laser_movable_system(
                 y: transform.translation.y as f64,
                 rot: transform.rotation.z as f64,
                 w: transform.rotation.w as f64,
+                weapon: *weapon,
+                // lets the other clients apply the same range and lifetime
+                start_x: Some(start_pos.x as f64),
+                start_y: Some(start_pos.y as f64),
+                age: now - spawned_at,
             })
             .ok();
 
@@ -903,31 +1340,70 @@ fn laser_movable_system(
     });
 }
 
-fn player_tag_animation_system(
-    mut animation_query: Query<
-        (&AnimateNameTranslation, &mut Transform),
-        (With<Text>, With<AnimateNameTranslation>),
-    >,
-    players_query: Query<
-        (Entity, &Transform),
-        (
-            Without<AnimateNameTranslation>,
-            Or<(With<LocalPlayer>, With<RemotePlayer>)>,
-        ),
-    >,
+fn player_tag_system(
+    camera_query: Query<&Transform, (With<Camera>, Without<PlayerTag>)>,
+    players_query: Query<(&Transform, Option<&Health>, Option<&PowerUp>), Without<PlayerTag>>,
+    mut tags_query: Query<(&Parent, &Children, &mut Transform), With<PlayerTag>>,
+    mut texts_query: Query<&mut Text>,
+    mut bars_query: Query<(&mut Sprite, &TagBar)>,
 ) {
-    for (animate_name_translation, mut animation_transform) in &mut animation_query {
-        // find the attached player
-        let attached_player = players_query
-            .iter()
-            .find(|(player_entity, player_transform)| {
-                animate_name_translation.0.eq(&player_entity)
-            });
-
-        // move the player text alongside with the transform coords of the entity
-        if let Some((attached_player_entity, attached_player_transform)) = attached_player {
-            animation_transform.translation.x = attached_player_transform.translation.x;
-            animation_transform.translation.y = attached_player_transform.translation.y;
+    let camera_position = camera_query
+        .get_single()
+        .map(|transform| transform.translation.truncate())
+        .unwrap_or(Vec2::ZERO);
+
+    for (parent, children, mut tag_transform) in tags_query.iter_mut() {
+        let (player_transform, health, power_up) = match players_query.get(parent.get()) {
+            Ok(player) => player,
+            Err(_) => continue,
+        };
+
+        // cancel the ship rotation and scale: the tag stays upright, at a fixed offset on screen
+        let inverse_rotation = player_transform.rotation.inverse();
+        tag_transform.rotation = inverse_rotation;
+        tag_transform.translation = (inverse_rotation * PLAYER_TAG_OFFSET) / player_transform.scale;
+        tag_transform.scale = Vec3::ONE / player_transform.scale;
+
+        // fade out with the distance to the camera
+        let (fade_start, fade_end) = PLAYER_TAG_FADE_DISTANCE;
+        let distance = player_transform
+            .translation
+            .truncate()
+            .distance(camera_position);
+        let alpha = 1.0 - ((distance - fade_start) / (fade_end - fade_start)).clamp(0.0, 1.0);
+
+        let health_ratio = health
+            .map(|health| health.current / health.max)
+            .unwrap_or(1.0);
+        let shield_ratio = power_up
+            .filter(|power_up| power_up.kind == CollectibleKind::Shield)
+            .map(|power_up| power_up.timer.percent_left())
+            .unwrap_or(0.0);
+
+        for child in children.iter() {
+            if let Ok(mut text) = texts_query.get_mut(*child) {
+                for section in text.sections.iter_mut() {
+                    section.style.color.set_a(alpha);
+                }
+            }
+            if let Ok((mut sprite, bar)) = bars_query.get_mut(*child) {
+                let (bar_width, bar_height) = PLAYER_TAG_BAR_SIZE;
+                match bar {
+                    TagBar::Background => {
+                        sprite.color.set_a(0.6 * alpha);
+                    }
+                    TagBar::Health => {
+                        // from green (full) to red (empty)
+                        sprite.color = Color::rgba(1.0 - health_ratio, health_ratio, 0.2, alpha);
+                        sprite.custom_size = Some(Vec2::new(bar_width * health_ratio, bar_height));
+                    }
+                    TagBar::Shield => {
+                        sprite.color.set_a(alpha);
+                        sprite.custom_size =
+                            Some(Vec2::new(bar_width * shield_ratio, bar_height / 2.0));
+                    }
+                }
+            }
         }
     }
 }
@@ -936,6 +1412,7 @@ fn explosion_to_spawn_system(
     mut commands: Commands,
     game_textures: Res<GameTextures>,
     query: Query<(Entity, &ExplosionToSpawn)>,
+    mut play_sound_events: EventWriter<PlaySound>,
 ) {
     for (explosion_spawn_entity, explosion_to_spawn) in query.iter() {
         // spawn the explosion sprite
@@ -950,6 +1427,12 @@ fn explosion_to_spawn_system(
             })
             .insert(Explosion)
             .insert(ExplosionTimer::default());
+        spawn_particle_burst(
+            &mut commands,
+            ParticleEffect::Explosion,
+            explosion_to_spawn.0,
+        );
+        play_sound_events.send(PlaySound::at(SoundEffect::Explosion, explosion_to_spawn.0));
 
         // despawn the explosionToSpawn
         commands.entity(explosion_spawn_entity).despawn();
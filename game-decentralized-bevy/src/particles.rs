@@ -0,0 +1,285 @@
+//! Lightweight CPU particles: a [`ParticleEmitter`] spawns untextured sprite particles, which move
+//! with a constant velocity and fade their colour and size over their lifetime. The total number
+//! of live particles is capped to keep the wasm build smooth.
+use bevy::prelude::*;
+use rand::Rng;
+
+use crate::components::Velocity;
+
+// live particles above this count make the emitters skip their emission
+const MAX_PARTICLES: usize = 600;
+// particles are drawn between the background and the ships
+const PARTICLE_Z: f32 = 0.5;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ParticleEffect {
+    EngineTrail,
+    ImpactSparks,
+    PickupBurst,
+    Explosion,
+}
+
+/// Emission parameters of an effect
+#[derive(Clone, Debug)]
+pub struct ParticleConfig {
+    /// lifetime range, in seconds
+    pub lifetime: (f32, f32),
+    /// speed range, in units per second
+    pub speed: (f32, f32),
+    /// half-angle (radians) of the emission cone around the emitter direction
+    pub spread: f32,
+    pub start_color: Color,
+    pub end_color: Color,
+    pub start_size: f32,
+    pub end_size: f32,
+}
+
+impl ParticleEffect {
+    pub fn config(&self) -> ParticleConfig {
+        match self {
+            ParticleEffect::EngineTrail => ParticleConfig {
+                lifetime: (0.25, 0.45),
+                speed: (30.0, 60.0),
+                spread: 0.25,
+                start_color: Color::rgba(1.0, 0.8, 0.3, 0.9),
+                end_color: Color::rgba(1.0, 0.2, 0.0, 0.0),
+                start_size: 6.0,
+                end_size: 2.0,
+            },
+            ParticleEffect::ImpactSparks => ParticleConfig {
+                lifetime: (0.15, 0.35),
+                speed: (80.0, 180.0),
+                spread: std::f32::consts::PI,
+                start_color: Color::rgba(0.8, 0.95, 1.0, 1.0),
+                end_color: Color::rgba(0.2, 0.5, 1.0, 0.0),
+                start_size: 4.0,
+                end_size: 1.0,
+            },
+            ParticleEffect::PickupBurst => ParticleConfig {
+                lifetime: (0.3, 0.6),
+                speed: (40.0, 100.0),
+                spread: std::f32::consts::PI,
+                start_color: Color::rgba(1.0, 0.9, 0.2, 1.0),
+                end_color: Color::rgba(0.4, 1.0, 0.4, 0.0),
+                start_size: 5.0,
+                end_size: 2.0,
+            },
+            ParticleEffect::Explosion => ParticleConfig {
+                lifetime: (0.4, 0.9),
+                speed: (20.0, 120.0),
+                spread: std::f32::consts::PI,
+                start_color: Color::rgba(1.0, 0.6, 0.1, 1.0),
+                end_color: Color::rgba(0.3, 0.3, 0.3, 0.0),
+                start_size: 8.0,
+                end_size: 3.0,
+            },
+        }
+    }
+
+    /// number of particles of a one-shot burst of this effect
+    pub fn burst_count(&self) -> u32 {
+        match self {
+            ParticleEffect::EngineTrail => 0,
+            ParticleEffect::ImpactSparks => 12,
+            ParticleEffect::PickupBurst => 20,
+            ParticleEffect::Explosion => 30,
+        }
+    }
+}
+
+/// Emits particles from the entity position, either continuously or as a one-shot burst
+#[derive(Component, Clone, Debug)]
+pub struct ParticleEmitter {
+    pub effect: ParticleEffect,
+    /// particles per second at full intensity
+    pub rate: f32,
+    /// emission rate factor in [0, 1]
+    pub intensity: f32,
+    /// emission point, relative to the entity (rotated with it)
+    pub offset: Vec3,
+    /// emission direction, relative to the entity (rotated with it)
+    pub direction: Vec2,
+    /// particles left to emit at once, the emitter entity is despawned afterwards
+    pub burst: Option<u32>,
+    accumulator: f32,
+}
+
+impl ParticleEmitter {
+    /// A continuous trail behind a ship, with an intensity following its speed
+    pub fn engine_trail() -> Self {
+        Self {
+            effect: ParticleEffect::EngineTrail,
+            rate: 60.0,
+            intensity: 0.0,
+            offset: Vec3::new(0.0, -28.0, 0.0),
+            direction: Vec2::new(0.0, -1.0),
+            burst: None,
+            accumulator: 0.0,
+        }
+    }
+
+    pub fn burst(effect: ParticleEffect) -> Self {
+        Self {
+            effect,
+            rate: 0.0,
+            intensity: 1.0,
+            offset: Vec3::ZERO,
+            direction: Vec2::Y,
+            burst: Some(effect.burst_count()),
+            accumulator: 0.0,
+        }
+    }
+}
+
+#[derive(Component, Clone, Debug)]
+pub struct Particle {
+    pub velocity: Vec2,
+    pub age: f32,
+    pub lifetime: f32,
+    pub start_color: Color,
+    pub end_color: Color,
+    pub start_size: f32,
+    pub end_size: f32,
+}
+
+pub struct ParticlesPlugin;
+
+impl Plugin for ParticlesPlugin {
+    fn build(
+        &self,
+        app: &mut App,
+    ) {
+        // particles are purely visual, they run every frame outside the fixed timestep
+        app.add_system(engine_trail_intensity_system);
+        app.add_system(particle_emitter_system);
+        app.add_system(particle_update_system);
+    }
+}
+
+/// Spawns a one-shot burst of particles at a game position
+pub fn spawn_particle_burst(
+    commands: &mut Commands,
+    effect: ParticleEffect,
+    position: Vec3,
+) {
+    commands
+        .spawn()
+        .insert(Transform::from_translation(position))
+        .insert(ParticleEmitter::burst(effect));
+}
+
+fn lerp(
+    (min, max): (f32, f32),
+    t: f32,
+) -> f32 {
+    min + (max - min) * t
+}
+
+fn lerp_color(
+    start: Color,
+    end: Color,
+    t: f32,
+) -> Color {
+    let (start, end) = (start.as_rgba_f32(), end.as_rgba_f32());
+    Color::rgba(
+        start[0] + (end[0] - start[0]) * t,
+        start[1] + (end[1] - start[1]) * t,
+        start[2] + (end[2] - start[2]) * t,
+        start[3] + (end[3] - start[3]) * t,
+    )
+}
+
+/// Ship trails get denser the faster the ship goes
+fn engine_trail_intensity_system(mut query: Query<(&Velocity, &mut ParticleEmitter)>) {
+    for (velocity, mut emitter) in query.iter_mut() {
+        if emitter.effect == ParticleEffect::EngineTrail && velocity.linear > 0.0 {
+            emitter.intensity = (velocity.vector.length() / velocity.linear).clamp(0.0, 1.0);
+        }
+    }
+}
+
+fn particle_emitter_system(
+    mut commands: Commands,
+    time: Res<Time>,
+    mut emitters_query: Query<(Entity, &Transform, &mut ParticleEmitter)>,
+    particles_query: Query<(), With<Particle>>,
+) {
+    let mut rng = rand::thread_rng();
+    let mut budget = MAX_PARTICLES.saturating_sub(particles_query.iter().count());
+
+    for (entity, transform, mut emitter) in emitters_query.iter_mut() {
+        let count = match emitter.burst {
+            Some(count) => {
+                commands.entity(entity).despawn();
+                count
+            }
+            None => {
+                emitter.accumulator += emitter.rate * emitter.intensity * time.delta_seconds();
+                let count = emitter.accumulator.floor();
+                emitter.accumulator -= count;
+                count as u32
+            }
+        };
+        if count == 0 {
+            continue;
+        }
+
+        // emitters are top-level entities (ships, bursts), their transform is in game space
+        let origin = transform.translation + transform.rotation * emitter.offset;
+        let direction = (transform.rotation * emitter.direction.extend(0.0)).truncate();
+        let config = emitter.effect.config();
+
+        for _ in 0..count {
+            if budget == 0 {
+                break;
+            }
+            budget -= 1;
+
+            let angle = lerp((-config.spread, config.spread), rng.gen());
+            let velocity = (Quat::from_rotation_z(angle) * direction.extend(0.0)).truncate()
+                * lerp(config.speed, rng.gen());
+            commands
+                .spawn_bundle(SpriteBundle {
+                    sprite: Sprite {
+                        color: config.start_color,
+                        custom_size: Some(Vec2::splat(config.start_size)),
+                        ..Default::default()
+                    },
+                    transform: Transform::from_xyz(origin.x, origin.y, PARTICLE_Z),
+                    ..Default::default()
+                })
+                .insert(Particle {
+                    velocity,
+                    age: 0.0,
+                    lifetime: lerp(config.lifetime, rng.gen()),
+                    start_color: config.start_color,
+                    end_color: config.end_color,
+                    start_size: config.start_size,
+                    end_size: config.end_size,
+                });
+        }
+    }
+}
+
+fn particle_update_system(
+    mut commands: Commands,
+    time: Res<Time>,
+    mut query: Query<(Entity, &mut Particle, &mut Transform, &mut Sprite)>,
+) {
+    let dt = time.delta_seconds();
+    for (entity, mut particle, mut transform, mut sprite) in query.iter_mut() {
+        particle.age += dt;
+        if particle.age >= particle.lifetime {
+            commands.entity(entity).despawn();
+            continue;
+        }
+
+        transform.translation += particle.velocity.extend(0.0) * dt;
+        let t = particle.age / particle.lifetime;
+        sprite.color = lerp_color(particle.start_color, particle.end_color, t);
+        sprite.custom_size = Some(Vec2::splat(lerp(
+            (particle.start_size, particle.end_size),
+            t,
+        )));
+    }
+}
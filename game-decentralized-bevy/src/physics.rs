@@ -0,0 +1,78 @@
+//! Inertia-based ship movement: thrust and brake accelerate the ship along its heading, drag slows
+//! it down, and rotation builds up angular velocity. The same model drives the local player and
+//! extrapolates remote players between two chain updates.
+use bevy::prelude::*;
+
+use crate::components::Velocity;
+use crate::{
+    BOUNDS, PLAYER_ANGULAR_ACCELERATION, PLAYER_ANGULAR_DRAG, PLAYER_BOOST_FACTOR,
+    PLAYER_BRAKE_DECELERATION, PLAYER_LINEAR_DRAG, PLAYER_THRUST_ACCELERATION,
+    POWER_UP_SPEED_FACTOR,
+};
+
+/// Controls applied to a ship for one simulation step
+#[derive(Clone, Debug, Default)]
+pub struct ShipControls {
+    /// rotation factor in [-1, 1], positive rotates the ship to the left
+    pub rotation: f32,
+    /// thrust factor in [0, 1]
+    pub thrust: f32,
+    pub brake: bool,
+    pub boost: bool,
+    /// a speed boost power-up is active
+    pub speed_boost: bool,
+}
+
+/// Advances a ship by `dt` seconds, updating both its `Velocity` and its `Transform`
+pub fn step_ship(
+    transform: &mut Transform,
+    velocity: &mut Velocity,
+    controls: &ShipControls,
+    dt: f32,
+) {
+    let mut boost_factor = if controls.boost {
+        PLAYER_BOOST_FACTOR
+    } else {
+        1.0
+    };
+    if controls.speed_boost {
+        boost_factor *= POWER_UP_SPEED_FACTOR;
+    }
+
+    // angular velocity: accelerate while rotating, damp otherwise
+    if controls.rotation != 0.0 {
+        velocity.angular += controls.rotation * PLAYER_ANGULAR_ACCELERATION * dt;
+    } else {
+        velocity.angular *= (1.0 - PLAYER_ANGULAR_DRAG * dt).max(0.0);
+    }
+    velocity.angular = velocity
+        .angular
+        .clamp(-velocity.rotational, velocity.rotational);
+    transform.rotation *= Quat::from_rotation_z(velocity.angular * dt);
+
+    // linear velocity: thrust along the heading, brake against the motion, then drag
+    let heading = (transform.rotation * Vec3::Y).truncate();
+    velocity.vector += heading * controls.thrust * PLAYER_THRUST_ACCELERATION * boost_factor * dt;
+    if controls.brake {
+        let speed = velocity.vector.length();
+        let braked_speed = (speed - PLAYER_BRAKE_DECELERATION * dt).max(0.0);
+        velocity.vector = velocity.vector.normalize_or_zero() * braked_speed;
+    }
+    velocity.vector *= (1.0 - PLAYER_LINEAR_DRAG * dt).max(0.0);
+    velocity.vector = velocity
+        .vector
+        .clamp_length_max(velocity.linear * boost_factor);
+
+    transform.translation += velocity.vector.extend(0.0) * dt;
+
+    // limit the movement within the screen, stopping the motion against the edges
+    let extents = Vec3::from((BOUNDS / 2.0, 0.0));
+    let clamped = transform.translation.clamp(-extents, extents);
+    if clamped.x != transform.translation.x {
+        velocity.vector.x = 0.0;
+    }
+    if clamped.y != transform.translation.y {
+        velocity.vector.y = 0.0;
+    }
+    transform.translation = clamped;
+}
@@ -0,0 +1,356 @@
+//! Plausibility checks of the remote player moves: each `PLAYER_MOVED` is checked against the
+//! movement model (max speed × elapsed time, max rotation speed, screen bounds) before being
+//! applied. Implausible moves are clamped or ignored, depending on the [`MovementPolicy`], and
+//! their players flagged on their name tags and in a report published to js. The elapsed time
+//! is measured between the chain times of the moves when they are timestamped: moves polled
+//! together arrive in the same frame, however far apart they were made.
+use bevy::prelude::*;
+
+use crate::components::{PlayerTag, RemotePlayer};
+use crate::resources::{
+    MovementPolicy, MovementValidation, MovementViolation, RemoteGamePlayerState, RemoteGameState,
+};
+use crate::wasm::{MOVEMENT_POLICY_REQUESTS, MOVEMENT_REPORT};
+use crate::{
+    BOUNDS, PLAUSIBILITY_FLAG_DURATION, PLAUSIBILITY_MIN_ELAPSED, PLAUSIBILITY_TOLERANCE,
+    PLAYER_BOOST_FACTOR, PLAYER_LINEAR_MOVEMENT_SPEED, PLAYER_LINEAR_ROTATION_SPEED,
+    POWER_UP_SPEED_FACTOR,
+};
+
+// interval between two reports published to js, in seconds
+const REPORT_INTERVAL: f64 = 1.0;
+// appended to the name tags of the flagged players
+const FLAGGED_TAG_SUFFIX: &str = " (!)";
+
+pub struct MovementValidationPlugin;
+
+impl Plugin for MovementValidationPlugin {
+    fn build(
+        &self,
+        app: &mut App,
+    ) {
+        app.init_resource::<MovementValidation>();
+        app.add_system(movement_policy_requests_system);
+        // after the gameplay systems, to see the moves applied this frame
+        app.add_system_to_stage(CoreStage::PostUpdate, movement_report_system);
+    }
+}
+
+/// Checks a remote move against the last applied one, records the result and applies the policy:
+/// returns the move to apply, if any. `now` is the game time (seconds since startup) and
+/// `event_time` the chain time (unix milliseconds) of the move, when timestamped.
+pub fn validate_remote_move(
+    validation: &mut MovementValidation,
+    previous: &RemoteGamePlayerState,
+    moved: RemoteGamePlayerState,
+    now: f64,
+    event_time: Option<f64>,
+) -> Option<RemoteGamePlayerState> {
+    let policy = validation.policy;
+    let record = validation.players.entry(moved.uuid.clone()).or_default();
+    // the first move is only checked against the bounds, there is no time reference yet
+    let elapsed = match (event_time, record.last_move_time) {
+        (Some(event_time), Some(last_move_time)) => Some((event_time - last_move_time) / 1000.0),
+        _ => record.last_move_at.map(|last_move_at| now - last_move_at),
+    }
+    .map(|elapsed| elapsed.max(PLAUSIBILITY_MIN_ELAPSED) as f32);
+    record.checked_moves += 1;
+
+    let violations = check_move(previous, &moved, elapsed);
+    if violations.is_empty() {
+        record.last_move_at = Some(now);
+        record.last_move_time = event_time;
+        return Some(moved);
+    }
+    record.implausible_moves += 1;
+    record.last_violation_at = Some(now);
+    record.last_violations = violations;
+    match policy {
+        MovementPolicy::Clamp => {
+            record.last_move_at = Some(now);
+            record.last_move_time = event_time;
+            Some(clamp_move(previous, moved, elapsed))
+        }
+        MovementPolicy::Ignore => None,
+    }
+}
+
+/// Max distance a ship can plausibly cover in `elapsed` seconds
+fn max_distance(elapsed: f32) -> f32 {
+    max_speed() * elapsed
+}
+
+/// Max speed of a ship, boosting with a speed power-up, with the tolerance
+fn max_speed() -> f32 {
+    PLAYER_LINEAR_MOVEMENT_SPEED
+        * PLAYER_BOOST_FACTOR
+        * POWER_UP_SPEED_FACTOR
+        * PLAUSIBILITY_TOLERANCE
+}
+
+/// Max angle a ship can plausibly rotate by in `elapsed` seconds
+fn max_rotation(elapsed: f32) -> f32 {
+    f32::to_radians(PLAYER_LINEAR_ROTATION_SPEED) * elapsed * PLAUSIBILITY_TOLERANCE
+}
+
+/// The chain sends the rotation as two raw components, which might not be normalized
+fn normalized(rotation: Quat) -> Quat {
+    if rotation.length_squared() > 0.0 {
+        rotation.normalize()
+    } else {
+        Quat::IDENTITY
+    }
+}
+
+fn check_move(
+    previous: &RemoteGamePlayerState,
+    moved: &RemoteGamePlayerState,
+    elapsed: Option<f32>,
+) -> Vec<MovementViolation> {
+    let mut violations = vec![];
+    let distance = moved
+        .position
+        .truncate()
+        .distance(previous.position.truncate());
+    let too_far = elapsed.map_or(false, |elapsed| distance > max_distance(elapsed));
+    if too_far || moved.velocity.length() > max_speed() {
+        violations.push(MovementViolation::Speed);
+    }
+    if let Some(elapsed) = elapsed {
+        let angle = normalized(previous.rotation).angle_between(normalized(moved.rotation));
+        if angle > max_rotation(elapsed) {
+            violations.push(MovementViolation::Rotation);
+        }
+    }
+    let extents = BOUNDS / 2.0;
+    if moved.position.x.abs() > extents.x || moved.position.y.abs() > extents.y {
+        violations.push(MovementViolation::OutOfBounds);
+    }
+    violations
+}
+
+/// Limits a move to the plausible distance, rotation, speed and bounds from the previous one
+fn clamp_move(
+    previous: &RemoteGamePlayerState,
+    moved: RemoteGamePlayerState,
+    elapsed: Option<f32>,
+) -> RemoteGamePlayerState {
+    let mut position = moved.position.truncate();
+    let mut rotation = moved.rotation;
+    if let Some(elapsed) = elapsed {
+        let origin = previous.position.truncate();
+        position = origin + (position - origin).clamp_length_max(max_distance(elapsed));
+
+        let from = normalized(previous.rotation);
+        let to = normalized(moved.rotation);
+        let angle = from.angle_between(to);
+        if angle > max_rotation(elapsed) {
+            rotation = from.slerp(to, max_rotation(elapsed) / angle);
+        }
+    }
+    let extents = BOUNDS / 2.0;
+    position = position.clamp(-extents, extents);
+    RemoteGamePlayerState {
+        position: position.extend(moved.position.z),
+        rotation,
+        velocity: moved.velocity.clamp_length_max(max_speed()),
+        ..moved
+    }
+}
+
+/// Applies the policy changes requested from js
+fn movement_policy_requests_system(mut validation: ResMut<MovementValidation>) {
+    let policy_requests = MOVEMENT_POLICY_REQUESTS.with(|requests| requests.take());
+    for policy in policy_requests.iter() {
+        match MovementPolicy::from_name(policy) {
+            Some(policy) => validation.policy = policy,
+            None => warn!("Invalid movement policy request {:?}", policy),
+        }
+    }
+}
+
+/// Flags the players with a recent implausible move on their name tags, and publishes the report
+/// to js every second
+fn movement_report_system(
+    time: Res<Time>,
+    mut last_report: Local<f64>,
+    mut validation: ResMut<MovementValidation>,
+    game_state: Res<RemoteGameState>,
+    players_query: Query<(&RemotePlayer, &Children)>,
+    tags_query: Query<&Children, With<PlayerTag>>,
+    mut texts_query: Query<&mut Text>,
+) {
+    let now = time.seconds_since_startup();
+
+    // forget the players which left, and flag the ones with a recent violation
+    validation
+        .players
+        .retain(|uuid, _| game_state.remote_players.contains_key(uuid));
+    for record in validation.players.values_mut() {
+        record.flagged = record
+            .last_violation_at
+            .map_or(false, |at| now - at < PLAUSIBILITY_FLAG_DURATION);
+    }
+
+    for (remote_player, children) in players_query.iter() {
+        let name = match game_state.remote_players.get(&remote_player.0) {
+            Some(player) => player.name.as_str(),
+            None => continue,
+        };
+        let flagged = validation
+            .players
+            .get(&remote_player.0)
+            .map_or(false, |record| record.flagged);
+        let tag = if flagged {
+            format!("{}{}", name, FLAGGED_TAG_SUFFIX)
+        } else {
+            name.to_string()
+        };
+        for tag_children in children
+            .iter()
+            .filter_map(|child| tags_query.get(*child).ok())
+        {
+            for tag_child in tag_children.iter() {
+                if let Ok(mut text) = texts_query.get_mut(*tag_child) {
+                    if let Some(section) = text.sections.first_mut() {
+                        if section.value != tag {
+                            section.value = tag.clone();
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    if now - *last_report >= REPORT_INTERVAL {
+        *last_report = now;
+        let report = serde_json::to_string(&*validation).unwrap_or_default();
+        MOVEMENT_REPORT.with(|published| *published.borrow_mut() = report);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::resources::EntityType;
+
+    const ADDRESS: &str = "AU1SkB92YpWm4Q2ijQHH34cqbKkCZWszsiQgHVjtNeFF2Ds6STz";
+
+    fn player(
+        x: f32,
+        y: f32,
+        angle: f32,
+    ) -> RemoteGamePlayerState {
+        RemoteGamePlayerState {
+            uuid: "player".to_string(),
+            address: ADDRESS.parse().unwrap(),
+            name: "player".to_string(),
+            position: Vec3::new(x, y, 0.0),
+            rotation: Quat::from_rotation_z(angle),
+            velocity: Vec2::ZERO,
+            skin: None,
+            r#type: EntityType::Remote,
+            seq: None,
+        }
+    }
+
+    #[test]
+    fn a_move_at_the_limits_is_plausible() {
+        let previous = player(0.0, 0.0, 0.0);
+        let moved = player(max_distance(1.0), 0.0, max_rotation(0.1) * 0.99);
+        assert!(check_move(&previous, &moved, Some(1.0)).is_empty());
+
+        let extents = BOUNDS / 2.0;
+        let at_bounds = player(extents.x, -extents.y, 0.0);
+        assert!(check_move(&at_bounds, &at_bounds, None).is_empty());
+    }
+
+    #[test]
+    fn a_move_past_the_limits_is_flagged() {
+        let previous = player(0.0, 0.0, 0.0);
+        let too_far = player(max_distance(1.0) + 1.0, 0.0, 0.0);
+        assert_eq!(
+            check_move(&previous, &too_far, Some(1.0)),
+            vec![MovementViolation::Speed]
+        );
+
+        let too_fast = RemoteGamePlayerState {
+            velocity: Vec2::new(max_speed() + 1.0, 0.0),
+            ..player(0.0, 0.0, 0.0)
+        };
+        assert_eq!(
+            check_move(&previous, &too_fast, None),
+            vec![MovementViolation::Speed]
+        );
+
+        let turned = player(0.0, 0.0, max_rotation(0.1) * 1.01);
+        assert_eq!(
+            check_move(&previous, &turned, Some(0.1)),
+            vec![MovementViolation::Rotation]
+        );
+
+        let outside = player(BOUNDS.x / 2.0 + 1.0, 0.0, 0.0);
+        assert_eq!(
+            check_move(&previous, &outside, None),
+            vec![MovementViolation::OutOfBounds]
+        );
+    }
+
+    #[test]
+    fn an_implausible_move_is_clamped_to_the_limits() {
+        let previous = player(0.0, 0.0, 0.0);
+        let moved = RemoteGamePlayerState {
+            velocity: Vec2::new(0.0, 2.0 * max_speed()),
+            ..player(0.0, 3.0 * max_distance(0.1), max_rotation(0.1) * 2.0)
+        };
+
+        let clamped = clamp_move(&previous, moved, Some(0.1));
+        assert!((clamped.position.y - max_distance(0.1)).abs() < 1e-3);
+        assert_eq!(clamped.position.x, 0.0);
+        let angle = previous.rotation.angle_between(clamped.rotation);
+        assert!((angle - max_rotation(0.1)).abs() < 1e-3);
+        assert!((clamped.velocity.length() - max_speed()).abs() < 1e-3);
+
+        // and kept within the bounds
+        let outside = player(BOUNDS.x, BOUNDS.y, 0.0);
+        let clamped = clamp_move(&outside, outside.clone(), None);
+        assert_eq!(clamped.position.truncate(), BOUNDS / 2.0);
+    }
+
+    #[test]
+    fn the_moves_polled_together_are_checked_against_their_chain_times() {
+        let mut validation = MovementValidation::default();
+        let start = player(0.0, 0.0, 0.0);
+        let first = player(100.0, 0.0, 0.0);
+        let second = player(300.0, 0.0, 0.0);
+
+        // made a second apart, received in the same frame: too far for the min elapsed time
+        assert!(
+            validate_remote_move(&mut validation, &start, first.clone(), 5.0, Some(1_000.0))
+                .is_some()
+        );
+        let applied = validate_remote_move(&mut validation, &first, second, 5.0, Some(2_000.0));
+        assert_eq!(applied.unwrap().position.x, 300.0);
+        let record = &validation.players["player"];
+        assert_eq!(record.implausible_moves, 0);
+        assert_eq!(record.last_move_time, Some(2_000.0));
+    }
+
+    #[test]
+    fn the_moves_without_chain_time_are_checked_against_the_game_time() {
+        let mut validation = MovementValidation::default();
+        let start = player(0.0, 0.0, 0.0);
+        let first = player(100.0, 0.0, 0.0);
+        let second = player(300.0, 0.0, 0.0);
+
+        validate_remote_move(&mut validation, &start, first.clone(), 5.0, None);
+        // 200 units in 0.5 seconds (the min elapsed time) is too fast, clamped
+        let applied = validate_remote_move(&mut validation, &first, second, 5.0, None).unwrap();
+        assert!((applied.position.x - (100.0 + max_distance(0.5))).abs() < 1e-3);
+        assert_eq!(validation.players["player"].implausible_moves, 1);
+
+        validation.policy = MovementPolicy::Ignore;
+        let far = player(1_000.0, 0.0, 0.0);
+        assert!(validate_remote_move(&mut validation, &applied, far, 5.1, None).is_none());
+    }
+}
@@ -0,0 +1,128 @@
+//! Typed collectibles: token value tiers and power-ups. A power-up takes effect on the collecting
+//! ship through a [`PowerUp`] component once the chain confirms the collection.
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+use std::time::Duration;
+
+use crate::components::Health;
+use crate::{COLLECTIBLE_SPRITE, POWER_UP_HEALTH_AMOUNT, TIME_STEP};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum CollectibleKind {
+    Token,
+    SilverToken,
+    GoldToken,
+    Shield,
+    SpeedBoost,
+    RapidFire,
+    Health,
+}
+
+impl Default for CollectibleKind {
+    fn default() -> Self {
+        CollectibleKind::Token
+    }
+}
+
+impl CollectibleKind {
+    pub const ALL: [CollectibleKind; 7] = [
+        CollectibleKind::Token,
+        CollectibleKind::SilverToken,
+        CollectibleKind::GoldToken,
+        CollectibleKind::Shield,
+        CollectibleKind::SpeedBoost,
+        CollectibleKind::RapidFire,
+        CollectibleKind::Health,
+    ];
+
+    pub fn sprite(&self) -> &'static str {
+        match self {
+            CollectibleKind::Token | CollectibleKind::SilverToken | CollectibleKind::GoldToken => {
+                COLLECTIBLE_SPRITE
+            }
+            CollectibleKind::Shield => "entities/powerup_shield.png",
+            CollectibleKind::SpeedBoost => "entities/powerup_speed.png",
+            CollectibleKind::RapidFire => "entities/powerup_rapid_fire.png",
+            CollectibleKind::Health => "entities/powerup_health.png",
+        }
+    }
+
+    /// sprite tint, telling the token tiers apart
+    pub fn tint(&self) -> Color {
+        match self {
+            CollectibleKind::SilverToken => Color::SILVER,
+            CollectibleKind::GoldToken => Color::GOLD,
+            _ => Color::WHITE,
+        }
+    }
+
+    /// effect duration in seconds, `None` for kinds without a timed effect
+    pub fn effect_duration(&self) -> Option<f32> {
+        match self {
+            CollectibleKind::Shield => Some(8.0),
+            CollectibleKind::SpeedBoost => Some(6.0),
+            CollectibleKind::RapidFire => Some(6.0),
+            _ => None,
+        }
+    }
+
+    pub fn is_power_up(&self) -> bool {
+        matches!(
+            self,
+            CollectibleKind::Shield
+                | CollectibleKind::SpeedBoost
+                | CollectibleKind::RapidFire
+                | CollectibleKind::Health
+        )
+    }
+}
+
+/// A power-up in effect on a ship. Collecting another one replaces it.
+#[derive(Component, Clone, Debug)]
+pub struct PowerUp {
+    pub kind: CollectibleKind,
+    pub timer: Timer,
+    /// whether the instant part of the effect (e.g. healing) has been applied
+    pub applied: bool,
+}
+
+impl PowerUp {
+    pub fn new(kind: CollectibleKind) -> Self {
+        Self {
+            kind,
+            timer: Timer::from_seconds(kind.effect_duration().unwrap_or(0.0), false),
+            applied: false,
+        }
+    }
+}
+
+/// Whether `power_up` is an active effect of the given kind
+pub fn is_power_up_active(
+    power_up: Option<&PowerUp>,
+    kind: CollectibleKind,
+) -> bool {
+    power_up
+        .map(|power_up| power_up.kind == kind && !power_up.timer.finished())
+        .unwrap_or(false)
+}
+
+/// Applies instant effects and removes the power-ups once they expire
+pub fn power_up_system(
+    mut commands: Commands,
+    mut query: Query<(Entity, &mut PowerUp, Option<&mut Health>)>,
+) {
+    for (entity, mut power_up, health) in query.iter_mut() {
+        if !power_up.applied {
+            power_up.applied = true;
+            if let (CollectibleKind::Health, Some(mut health)) = (power_up.kind, health) {
+                health.heal(POWER_UP_HEALTH_AMOUNT);
+            }
+        }
+
+        power_up.timer.tick(Duration::from_secs_f32(TIME_STEP));
+        if power_up.timer.finished() {
+            commands.entity(entity).remove::<PowerUp>();
+        }
+    }
+}
@@ -0,0 +1,387 @@
+//! Reconciliation of the periodic full state polls: the latest players and collectibles are
+//! diffed against the [`RemoteGameState`] and turned into the incremental updates which bring
+//! the game in line with the chain, so a missed event cannot leave a ghost or a missing entity.
+//! The polls and the events are fetched independently, so a poll can be older than the events
+//! already applied: a polled entry older than the last update applied to its entity is skipped,
+//! and an entity is only removed when no update newer than the poll was applied to it (the poll
+//! is as recent as its most recent entry). The updates left are applied as is, without another
+//! sequence check (a removal would otherwise reuse the sequence already applied).
+use crate::resources::{
+    player_stream, token_stream, EntityType, RemoteCollectibleState, RemoteGamePlayerState,
+    RemoteGameState, RemoteStateType,
+};
+use crate::utils::keyed_diff;
+
+// distance between the known and the polled position of a player past which the player is moved,
+// closer ones are left to the incremental moves (the ships keep moving between the polls)
+const RECONCILE_POSITION_TOLERANCE: f32 = 50.0;
+
+/// Whether a polled entry is older than the last update applied to its entity
+fn is_outdated(
+    game_state: &RemoteGameState,
+    stream: &str,
+    seq: Option<u64>,
+) -> bool {
+    match (game_state.last_applied_sequence(stream), seq) {
+        (Some(applied), Some(seq)) => seq < applied,
+        _ => false,
+    }
+}
+
+/// Whether an entity missing from a poll as recent as `poll_seq` can be removed: not when an
+/// update newer than the poll was applied to it
+fn is_removable(
+    game_state: &RemoteGameState,
+    stream: &str,
+    poll_seq: Option<u64>,
+) -> bool {
+    match (game_state.last_applied_sequence(stream), poll_seq) {
+        (Some(applied), Some(poll_seq)) => applied <= poll_seq,
+        _ => true,
+    }
+}
+
+/// Updates turning the known remote players into the polled ones: the missing players are
+/// added, the gone ones removed and the ones far from their polled position moved. The local
+/// player is skipped.
+pub fn reconcile_players(
+    game_state: &RemoteGameState,
+    latest: Vec<RemoteGamePlayerState>,
+) -> Vec<RemoteStateType> {
+    let local_player_uuid = game_state
+        .local_player
+        .as_ref()
+        .map(|local_player| local_player.uuid.clone());
+    let poll_seq = latest.iter().filter_map(|player| player.seq).max();
+    let latest = latest
+        .into_iter()
+        .filter(|player| Some(&player.uuid) != local_player_uuid.as_ref())
+        .map(|player| RemoteGamePlayerState {
+            r#type: EntityType::Remote,
+            ..player
+        })
+        .collect::<Vec<RemoteGamePlayerState>>();
+
+    let diff = keyed_diff(
+        &game_state.remote_players,
+        latest,
+        |player| player.uuid.clone(),
+        |current, latest| current.position.distance(latest.position) > RECONCILE_POSITION_TOLERANCE,
+    );
+
+    let mut updates = vec![];
+    updates.extend(
+        diff.removed
+            .iter()
+            .filter(|uuid| is_removable(game_state, &player_stream(uuid), poll_seq))
+            .filter_map(|uuid| game_state.remote_players.get(uuid).cloned())
+            .map(|player| RemoteStateType::PlayerRemoved(unsequenced_player(player))),
+    );
+    let is_current = |player: &RemoteGamePlayerState| {
+        !is_outdated(game_state, &player_stream(&player.uuid), player.seq)
+    };
+    updates.extend(
+        diff.added
+            .into_iter()
+            .filter(is_current)
+            .map(|player| RemoteStateType::PlayerAdded(unsequenced_player(player))),
+    );
+    updates.extend(
+        diff.changed
+            .into_iter()
+            .filter(is_current)
+            .map(|player| RemoteStateType::PlayerMoved(unsequenced_player(player))),
+    );
+    updates
+}
+
+/// Updates turning the known collectibles into the polled ones. A collectible whose position or
+/// kind changed is removed and added back, as there is no update event for the tokens.
+pub fn reconcile_collectibles(
+    game_state: &RemoteGameState,
+    latest: Vec<RemoteCollectibleState>,
+) -> Vec<RemoteStateType> {
+    let poll_seq = latest
+        .iter()
+        .filter_map(|collectible| collectible.seq)
+        .max();
+    let diff = keyed_diff(
+        &game_state.remote_collectibles,
+        latest,
+        |collectible| collectible.uuid.clone(),
+        |current, latest| current.position != latest.position || current.kind != latest.kind,
+    );
+
+    let mut updates = vec![];
+    updates.extend(
+        diff.removed
+            .iter()
+            .filter(|uuid| is_removable(game_state, &token_stream(uuid), poll_seq))
+            .filter_map(|uuid| game_state.remote_collectibles.get(uuid).cloned())
+            .map(|collectible| RemoteStateType::TokenRemoved(unsequenced_collectible(collectible))),
+    );
+    let is_current = |collectible: &RemoteCollectibleState| {
+        !is_outdated(
+            game_state,
+            &token_stream(&collectible.uuid),
+            collectible.seq,
+        )
+    };
+    for collectible in diff.changed.into_iter().filter(is_current) {
+        if let Some(current) = game_state.remote_collectibles.get(&collectible.uuid) {
+            updates.push(RemoteStateType::TokenRemoved(unsequenced_collectible(
+                current.clone(),
+            )));
+        }
+        updates.push(RemoteStateType::TokenAdded(unsequenced_collectible(
+            collectible,
+        )));
+    }
+    updates.extend(
+        diff.added
+            .into_iter()
+            .filter(is_current)
+            .map(|collectible| RemoteStateType::TokenAdded(unsequenced_collectible(collectible))),
+    );
+    updates
+}
+
+fn unsequenced_player(player: RemoteGamePlayerState) -> RemoteGamePlayerState {
+    RemoteGamePlayerState {
+        seq: None,
+        ..player
+    }
+}
+
+fn unsequenced_collectible(collectible: RemoteCollectibleState) -> RemoteCollectibleState {
+    RemoteCollectibleState {
+        seq: None,
+        ..collectible
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy::prelude::{Quat, Vec2, Vec3};
+
+    use super::*;
+    use crate::powerups::CollectibleKind;
+    use crate::MAX_REMOVED_SEQUENCES;
+
+    const ADDRESS: &str = "AU1SkB92YpWm4Q2ijQHH34cqbKkCZWszsiQgHVjtNeFF2Ds6STz";
+
+    fn player(
+        uuid: &str,
+        x: f32,
+        seq: u64,
+    ) -> RemoteGamePlayerState {
+        RemoteGamePlayerState {
+            uuid: uuid.to_string(),
+            address: ADDRESS.parse().unwrap(),
+            name: uuid.to_string(),
+            position: Vec3::new(x, 0.0, 0.0),
+            rotation: Quat::IDENTITY,
+            velocity: Vec2::ZERO,
+            skin: None,
+            r#type: EntityType::Remote,
+            seq: Some(seq),
+        }
+    }
+
+    fn collectible(
+        uuid: &str,
+        x: f32,
+        seq: u64,
+    ) -> RemoteCollectibleState {
+        RemoteCollectibleState {
+            uuid: uuid.to_string(),
+            position: Vec3::new(x, 0.0, 0.0),
+            kind: CollectibleKind::Token,
+            seq: Some(seq),
+        }
+    }
+
+    // the state after applying the given sequenced updates, as the update system does
+    fn sequenced_state(updates: &[RemoteStateType]) -> RemoteGameState {
+        let mut game_state = RemoteGameState::default();
+        for update in updates.iter() {
+            assert!(game_state.accept_update_sequence(update));
+            match update {
+                RemoteStateType::PlayerAdded(player) => {
+                    game_state
+                        .remote_players
+                        .insert(player.uuid.clone(), player.clone());
+                }
+                RemoteStateType::TokenAdded(collectible) => {
+                    game_state
+                        .remote_collectibles
+                        .insert(collectible.uuid.clone(), collectible.clone());
+                }
+                _ => unreachable!(),
+            }
+        }
+        game_state
+    }
+
+    #[test]
+    fn players_are_removed_moved_and_added_past_the_sequence_check() {
+        let mut game_state = sequenced_state(&[
+            RemoteStateType::PlayerAdded(player("gone", 0.0, 5)),
+            RemoteStateType::PlayerAdded(player("moved", 0.0, 7)),
+            RemoteStateType::PlayerAdded(player("still", 0.0, 3)),
+        ]);
+
+        // the polled state carries the sequences the state already applied, or newer ones
+        let updates = reconcile_players(
+            &game_state,
+            vec![
+                player("moved", 200.0, 8),
+                player("still", 0.0, 3),
+                player("new", 0.0, 1),
+            ],
+        );
+
+        assert_eq!(updates.len(), 3);
+        assert!(matches!(&updates[0], RemoteStateType::PlayerRemoved(p) if p.uuid == "gone"));
+        assert!(matches!(&updates[1], RemoteStateType::PlayerAdded(p) if p.uuid == "new"));
+        assert!(matches!(
+            &updates[2],
+            RemoteStateType::PlayerMoved(p) if p.uuid == "moved" && p.position.x == 200.0
+        ));
+        for update in updates.iter() {
+            assert!(game_state.accept_update_sequence(update));
+        }
+        assert_eq!(game_state.stale_updates, 0);
+    }
+
+    #[test]
+    fn the_local_player_is_not_reconciled() {
+        let mut game_state = RemoteGameState::default();
+        game_state.local_player = Some(player("local", 0.0, 1));
+
+        let updates = reconcile_players(&game_state, vec![player("local", 10.0, 2)]);
+        assert!(updates.is_empty());
+    }
+
+    #[test]
+    fn collectibles_are_removed_replaced_and_added_past_the_sequence_check() {
+        let mut game_state = sequenced_state(&[
+            RemoteStateType::TokenAdded(collectible("gone", 0.0, 2)),
+            RemoteStateType::TokenAdded(collectible("changed", 0.0, 4)),
+            RemoteStateType::TokenAdded(collectible("still", 0.0, 1)),
+        ]);
+
+        let updates = reconcile_collectibles(
+            &game_state,
+            vec![
+                collectible("changed", 10.0, 6),
+                collectible("still", 0.0, 1),
+                collectible("new", 0.0, 1),
+            ],
+        );
+
+        // a changed token is removed then added back, both applied
+        assert_eq!(updates.len(), 4);
+        assert!(matches!(&updates[0], RemoteStateType::TokenRemoved(c) if c.uuid == "gone"));
+        assert!(matches!(
+            &updates[1],
+            RemoteStateType::TokenRemoved(c) if c.uuid == "changed" && c.position.x == 0.0
+        ));
+        assert!(matches!(
+            &updates[2],
+            RemoteStateType::TokenAdded(c) if c.uuid == "changed" && c.position.x == 10.0
+        ));
+        assert!(matches!(&updates[3], RemoteStateType::TokenAdded(c) if c.uuid == "new"));
+        for update in updates.iter() {
+            assert!(game_state.accept_update_sequence(update));
+        }
+        assert_eq!(game_state.stale_updates, 0);
+    }
+
+    #[test]
+    fn a_player_drifting_within_the_tolerance_is_left_to_the_moves() {
+        let game_state = sequenced_state(&[RemoteStateType::PlayerAdded(player("moving", 0.0, 1))]);
+        let updates = reconcile_players(&game_state, vec![player("moving", 30.0, 2)]);
+        assert!(updates.is_empty());
+    }
+
+    #[test]
+    fn a_poll_older_than_the_applied_updates_does_not_rewind_them() {
+        let game_state = sequenced_state(&[
+            RemoteStateType::PlayerAdded(player("early", 0.0, 3)),
+            RemoteStateType::PlayerAdded(player("moved", 300.0, 8)),
+            RemoteStateType::PlayerAdded(player("late", 0.0, 9)),
+        ]);
+
+        // polled at 6: "moved" was at 0 then, "late" not added yet
+        let updates = reconcile_players(
+            &game_state,
+            vec![player("early", 0.0, 3), player("moved", 0.0, 6)],
+        );
+        assert!(updates.is_empty());
+    }
+
+    #[test]
+    fn a_poll_older_than_the_applied_updates_does_not_remove_or_revive_tokens() {
+        let mut game_state = sequenced_state(&[
+            RemoteStateType::TokenAdded(collectible("early", 0.0, 3)),
+            RemoteStateType::TokenAdded(collectible("collected", 50.0, 8)),
+            RemoteStateType::TokenAdded(collectible("late", 0.0, 9)),
+        ]);
+        // collected at 8, after the poll
+        game_state.remove_collectible("collected");
+
+        let updates = reconcile_collectibles(
+            &game_state,
+            vec![
+                collectible("early", 0.0, 3),
+                collectible("collected", 50.0, 5),
+            ],
+        );
+        assert!(updates.is_empty());
+
+        // a newer poll still removes
+        let updates = reconcile_collectibles(&game_state, vec![collectible("early", 0.0, 10)]);
+        assert_eq!(updates.len(), 1);
+        assert!(matches!(&updates[0], RemoteStateType::TokenRemoved(c) if c.uuid == "late"));
+    }
+
+    #[test]
+    fn a_removed_entity_is_not_revived_by_a_late_update() {
+        let mut game_state =
+            sequenced_state(&[RemoteStateType::PlayerAdded(player("gone", 0.0, 5))]);
+        game_state.remove_remote_player("gone");
+        assert!(game_state.applied_sequences.is_empty());
+
+        assert!(!game_state
+            .accept_update_sequence(&RemoteStateType::PlayerAdded(player("gone", 0.0, 4))));
+        assert_eq!(game_state.stale_updates, 1);
+        assert!(game_state
+            .accept_update_sequence(&RemoteStateType::PlayerAdded(player("gone", 0.0, 6))));
+        assert!(game_state.removed_sequences.is_empty());
+    }
+
+    #[test]
+    fn the_removed_streams_are_bounded() {
+        let mut game_state = RemoteGameState::default();
+        for seq in 0..(MAX_REMOVED_SEQUENCES as u64 + 10) {
+            let uuid = format!("token-{}", seq);
+            assert!(
+                game_state.accept_update_sequence(&RemoteStateType::TokenAdded(collectible(
+                    &uuid, 0.0, seq
+                )))
+            );
+            game_state.remove_collectible(&uuid);
+        }
+        assert_eq!(game_state.removed_sequences.len(), MAX_REMOVED_SEQUENCES);
+        // the oldest are forgotten first
+        assert_eq!(
+            game_state.last_applied_sequence(&token_stream("token-0")),
+            None
+        );
+        assert_eq!(
+            game_state.last_applied_sequence(&token_stream("token-10")),
+            Some(10)
+        );
+    }
+}
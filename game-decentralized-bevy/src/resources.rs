@@ -6,15 +6,42 @@ use serde::{Deserialize, Serialize};
 use std::collections::BTreeMap;
 use wasm_bindgen::JsValue;
 
+use crate::address::MassaAddress;
+use crate::errors::ClientError;
 use crate::events::PlayerLaserSerializedData;
+use crate::powerups::CollectibleKind;
+use crate::weapons::WeaponKind;
+use crate::MAX_REMOVED_SEQUENCES;
 
 #[derive(Debug, Clone)]
 pub struct GameTextures {
     pub player: HashMap<String, Handle<Image>>,
     pub laser: Handle<Image>,
-    pub collectible: Handle<Image>,
+    pub collectible: HashMap<CollectibleKind, Handle<Image>>,
     pub background: Handle<Image>,
     pub explosion: Handle<TextureAtlas>,
+    /// image of the explosion texture atlas
+    pub explosion_sheet: Handle<Image>,
+    pub font: Handle<Font>,
+}
+
+impl GameTextures {
+    /// All the loaded asset handles, to track their loading
+    pub fn handles(&self) -> Vec<HandleUntyped> {
+        let mut handles = vec![
+            self.laser.clone_untyped(),
+            self.background.clone_untyped(),
+            self.explosion_sheet.clone_untyped(),
+            self.font.clone_untyped(),
+        ];
+        handles.extend(self.player.values().map(|handle| handle.clone_untyped()));
+        handles.extend(
+            self.collectible
+                .values()
+                .map(|handle| handle.clone_untyped()),
+        );
+        handles
+    }
 }
 
 #[derive(Debug, Clone)]
@@ -23,6 +50,381 @@ pub struct WinSize {
     pub h: f32,
 }
 
+/// Local player intents, merged from all input devices (keyboard, gamepad)
+#[derive(Clone, Debug, Default)]
+pub struct InputActions {
+    /// rotation factor in [-1, 1], positive rotates the ship to the left
+    pub rotation: f32,
+    /// thrust factor in [0, 1]
+    pub thrust: f32,
+    pub brake: bool,
+    /// a shot was requested and has not been fired yet
+    pub fire: bool,
+    /// the fire input is held down (charges the beam weapon)
+    pub fire_held: bool,
+    /// a weapon switch was requested and has not been applied yet
+    pub switch_weapon: bool,
+    pub boost: bool,
+    /// the scoreboard toggle was pressed this frame
+    pub toggle_scoreboard: bool,
+    /// the diagnostics overlay toggle was pressed this frame
+    pub toggle_diagnostics: bool,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+pub enum InputAction {
+    RotateLeft,
+    RotateRight,
+    Thrust,
+    Brake,
+    Fire,
+    SwitchWeapon,
+    Boost,
+    ToggleScoreboard,
+    ToggleDiagnostics,
+}
+
+impl InputAction {
+    pub const ALL: [InputAction; 9] = [
+        InputAction::RotateLeft,
+        InputAction::RotateRight,
+        InputAction::Thrust,
+        InputAction::Brake,
+        InputAction::Fire,
+        InputAction::SwitchWeapon,
+        InputAction::Boost,
+        InputAction::ToggleScoreboard,
+        InputAction::ToggleDiagnostics,
+    ];
+
+    pub fn from_name(name: &str) -> Option<InputAction> {
+        InputAction::ALL
+            .iter()
+            .find(|action| format!("{:?}", action).eq(name))
+            .copied()
+    }
+}
+
+/// Keyboard key bound to each input action
+#[derive(Clone, Debug)]
+pub struct InputBindings(pub BTreeMap<InputAction, KeyCode>);
+
+impl Default for InputBindings {
+    fn default() -> Self {
+        Self(BTreeMap::from([
+            (InputAction::RotateLeft, KeyCode::Left),
+            (InputAction::RotateRight, KeyCode::Right),
+            (InputAction::Thrust, KeyCode::Up),
+            (InputAction::Brake, KeyCode::Down),
+            (InputAction::Fire, KeyCode::Space),
+            (InputAction::SwitchWeapon, KeyCode::Q),
+            (InputAction::Boost, KeyCode::LShift),
+            (InputAction::ToggleScoreboard, KeyCode::Tab),
+            (InputAction::ToggleDiagnostics, KeyCode::F3),
+        ]))
+    }
+}
+
+impl InputBindings {
+    pub fn key(
+        &self,
+        action: InputAction,
+    ) -> Option<KeyCode> {
+        self.0.get(&action).copied()
+    }
+
+    pub fn pressed(
+        &self,
+        action: InputAction,
+        keyboard_input: &Input<KeyCode>,
+    ) -> bool {
+        self.key(action)
+            .map(|key| keyboard_input.pressed(key))
+            .unwrap_or(false)
+    }
+
+    pub fn just_pressed(
+        &self,
+        action: InputAction,
+        keyboard_input: &Input<KeyCode>,
+    ) -> bool {
+        self.key(action)
+            .map(|key| keyboard_input.just_pressed(key))
+            .unwrap_or(false)
+    }
+
+    /// Binds `key` to `action`. An action already using `key` gets the previous key of `action`
+    /// (the bindings are swapped) and is returned.
+    pub fn rebind(
+        &mut self,
+        action: InputAction,
+        key: KeyCode,
+    ) -> Option<InputAction> {
+        let previous_key = self.0.insert(action, key);
+        let swapped_action = self
+            .0
+            .iter()
+            .find(|(other_action, other_key)| **other_action != action && **other_key == key)
+            .map(|(other_action, _)| *other_action);
+        match (swapped_action, previous_key) {
+            (Some(swapped_action), Some(previous_key)) => {
+                self.0.insert(swapped_action, previous_key);
+            }
+            (Some(swapped_action), None) => {
+                self.0.remove(&swapped_action);
+            }
+            _ => {}
+        }
+        swapped_action
+    }
+}
+
+/// Whether touch input has been detected (the on-screen controls are shown from then on)
+#[derive(Clone, Debug, Default)]
+pub struct TouchControls {
+    pub detected: bool,
+}
+
+/// The gamepad currently controlling the local player, if any
+#[derive(Clone, Debug, Default)]
+pub struct ActiveGamepad(pub Option<Gamepad>);
+
+/// Visibility of the browser page and focus of the game window
+#[derive(Clone, Debug)]
+pub struct PageActivity {
+    pub hidden: bool,
+    pub focused: bool,
+}
+
+impl Default for PageActivity {
+    fn default() -> Self {
+        Self {
+            hidden: false,
+            focused: true,
+        }
+    }
+}
+
+/// Audio volumes in [0, 1], adjustable from js
+#[derive(Clone, Debug)]
+pub struct AudioSettings {
+    pub master: f32,
+    pub sfx: f32,
+    pub music: f32,
+    /// whether the browser lets audio play yet (always true on native)
+    pub unlocked: bool,
+}
+
+impl Default for AudioSettings {
+    fn default() -> Self {
+        Self {
+            master: 1.0,
+            sfx: 0.8,
+            music: 0.5,
+            unlocked: !cfg!(target_arch = "wasm32"),
+        }
+    }
+}
+
+impl AudioSettings {
+    /// Sets the volume of a channel ("master", "sfx" or "music"), returns false for an unknown
+    /// channel
+    pub fn set_volume(
+        &mut self,
+        channel: &str,
+        volume: f32,
+    ) -> bool {
+        let volume = volume.clamp(0.0, 1.0);
+        match channel {
+            "master" => self.master = volume,
+            "sfx" => self.sfx = volume,
+            "music" => self.music = volume,
+            _ => return false,
+        }
+        true
+    }
+}
+
+/// Whether the diagnostics overlay is shown
+#[derive(Clone, Debug, Default)]
+pub struct DiagnosticsOverlay {
+    pub visible: bool,
+}
+
+/// Whether the local ship and the lasers are simulated by the deterministic fixed-point core
+#[derive(Clone, Debug, Default)]
+pub struct DeterministicSimulation {
+    pub enabled: bool,
+}
+
+/// Whether the scoreboard is shown (always while spectating)
+#[derive(Clone, Debug, Default)]
+pub struct Scoreboard {
+    pub visible: bool,
+}
+
+/// Camera of the spectator mode, free or following a remote player
+#[derive(Clone, Debug, Default)]
+pub struct SpectatorCamera {
+    /// uuid of the followed player, the camera is free when none
+    pub followed: Option<String>,
+}
+
+/// Inbound network and runtime metrics, shown by the diagnostics overlay and published to js
+#[derive(Clone, Debug, Default, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct NetworkMetrics {
+    /// inbound updates per second, by operation (e.g. "PLAYER_MOVED")
+    pub updates_per_second: BTreeMap<String, f64>,
+    /// updates queued by js when last drained
+    pub queue_depth: usize,
+    /// updates dropped because the inbound queue was full, since the start
+    pub overflowed_updates: u64,
+    /// queued updates replaced by a newer one of the same player, since the start
+    pub coalesced_updates: u64,
+    /// updates older than the queued one of the same player, dropped, since the start
+    pub discarded_updates: u64,
+    /// updates which could not be decoded, since the start
+    pub decode_errors: u64,
+    /// updates of an operation unknown to the game, since the start
+    pub unknown_operations: u64,
+    /// out-of-order or duplicate updates dropped, since the start
+    pub stale_updates: u64,
+    /// seconds since the last update of each remote player, by uuid
+    pub player_update_ages: BTreeMap<String, f64>,
+    /// smoothed delay between the chain time of the timestamped events and their reception, in
+    /// milliseconds
+    pub chain_latency_ms: Option<f64>,
+    pub fps: Option<f64>,
+    /// live entities, by kind
+    pub entity_counts: BTreeMap<String, usize>,
+    /// updates received in the current one second window, by operation
+    #[serde(skip)]
+    pub window_counts: BTreeMap<String, u32>,
+    /// game time (seconds since startup) of the last update of each remote player, by uuid
+    #[serde(skip)]
+    pub player_updated_at: HashMap<String, f64>,
+}
+
+impl NetworkMetrics {
+    /// Records a received update as mapped from js, `event_time` is the chain time (unix
+    /// milliseconds) of the timestamped events. An update without data is not an error.
+    pub fn record_update(
+        &mut self,
+        operation: &str,
+        update: &Result<Option<RemoteStateType>, ClientError>,
+        event_time: Option<f64>,
+        now: f64,
+        now_unix_millis: f64,
+    ) {
+        *self.window_counts.entry(operation.to_string()).or_insert(0) += 1;
+
+        let player_uuid = match update {
+            Err(ClientError::UnknownOperationReceived) => {
+                self.unknown_operations += 1;
+                return;
+            }
+            Err(_) => {
+                self.decode_errors += 1;
+                return;
+            }
+            Ok(Some(
+                RemoteStateType::PlayerAdded(player) | RemoteStateType::PlayerMoved(player),
+            )) => match player.r#type {
+                EntityType::Remote => Some(&player.uuid),
+                EntityType::Local => None,
+            },
+            Ok(Some(RemoteStateType::LasersShot((player_uuid, ..)))) => Some(player_uuid),
+            Ok(_) => None,
+        };
+        if let Some(player_uuid) = player_uuid {
+            self.player_updated_at.insert(player_uuid.clone(), now);
+        }
+
+        // the clocks are not synchronized, negative delays are clamped
+        if let Some(event_time) = event_time {
+            let latency = (now_unix_millis - event_time).max(0.0);
+            self.chain_latency_ms = Some(match self.chain_latency_ms {
+                Some(smoothed) => smoothed * 0.9 + latency * 0.1,
+                None => latency,
+            });
+        }
+    }
+}
+
+/// What to do with the remote moves failing the plausibility checks
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum MovementPolicy {
+    /// the move is applied, limited to the plausible speed, rotation and bounds
+    Clamp,
+    /// the move is dropped, the player keeps being extrapolated from its last plausible move
+    Ignore,
+}
+
+impl MovementPolicy {
+    pub fn from_name(name: &str) -> Option<MovementPolicy> {
+        match name {
+            "clamp" => Some(MovementPolicy::Clamp),
+            "ignore" => Some(MovementPolicy::Ignore),
+            _ => None,
+        }
+    }
+}
+
+/// A plausibility check of a remote move, against the movement model
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum MovementViolation {
+    /// moved farther, or faster, than the max speed allows
+    Speed,
+    /// rotated faster than the max rotation speed allows
+    Rotation,
+    /// moved outside of the screen
+    OutOfBounds,
+}
+
+/// Plausibility record of a remote player
+#[derive(Clone, Debug, Default, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PlayerPlausibility {
+    /// moves checked, since the player joined
+    pub checked_moves: u64,
+    /// moves failing a check, since the player joined
+    pub implausible_moves: u64,
+    /// checks failed by the last implausible move
+    pub last_violations: Vec<MovementViolation>,
+    /// the player had an implausible move recently
+    pub flagged: bool,
+    /// game time (seconds since startup) of the last implausible move
+    #[serde(skip)]
+    pub last_violation_at: Option<f64>,
+    /// game time (seconds since startup) of the last applied move
+    #[serde(skip)]
+    pub last_move_at: Option<f64>,
+    /// chain time (unix milliseconds) of the last applied move, when timestamped
+    #[serde(skip)]
+    pub last_move_time: Option<f64>,
+}
+
+/// Plausibility checks of the remote player moves, flagged on the name tags and published to js
+#[derive(Clone, Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct MovementValidation {
+    pub policy: MovementPolicy,
+    /// records of the remote players, by uuid
+    pub players: BTreeMap<String, PlayerPlausibility>,
+}
+
+impl Default for MovementValidation {
+    fn default() -> Self {
+        Self {
+            policy: MovementPolicy::Clamp,
+            players: BTreeMap::new(),
+        }
+    }
+}
+
 #[derive(Clone, Debug)]
 pub enum EntityType {
     Local,
@@ -32,13 +434,19 @@ pub enum EntityType {
 #[derive(Clone, Debug)]
 pub struct RemoteGamePlayerState {
     pub uuid: String,
-    pub address: String,
+    pub address: MassaAddress,
     pub name: String,
     /// linear speed in meters per second
     pub position: Vec3,
     /// rotation speed in radians per second
     pub rotation: Quat,
+    /// linear velocity in units per second
+    pub velocity: Vec2,
+    /// name of the skin picked by the player, if any
+    pub skin: Option<String>,
     pub r#type: EntityType,
+    /// sequence number of the event, if sent
+    pub seq: Option<u64>,
 }
 
 #[derive(Clone, Debug, Serialize, Deserialize)]
@@ -50,6 +458,8 @@ pub struct RemoteLaserState {
     pub y: f64,
     pub rot: f64,
     pub w: f64,
+    #[serde(default)]
+    pub weapon: WeaponKind,
 }
 
 #[derive(Clone, Debug, Serialize)]
@@ -59,6 +469,8 @@ pub struct CollectedEntity {
     pub player_uuid: String,
     pub value: f64,
     pub time: f64,
+    /// sequence number of the event, if sent
+    pub seq: Option<u64>,
 }
 
 #[derive(Clone, Debug)]
@@ -66,6 +478,18 @@ pub struct RemoteCollectibleState {
     pub uuid: String,
     /// position of the token
     pub position: Vec3,
+    pub kind: CollectibleKind,
+    /// sequence number of the event, if sent
+    pub seq: Option<u64>,
+}
+
+/// A locally predicted token collection, awaiting confirmation from the chain
+#[derive(Clone, Debug)]
+pub struct PendingCollectibleClaim {
+    /// uuid of the player predicted to have collected the token
+    pub player_uuid: String,
+    /// game time (seconds since startup) at which the prediction was made
+    pub claimed_at: f64,
 }
 
 #[derive(Clone, Debug)]
@@ -76,7 +500,49 @@ pub enum RemoteStateType {
     TokenCollected(CollectedEntity),
     TokenAdded(RemoteCollectibleState),
     TokenRemoved(RemoteCollectibleState),
-    LasersShot((String, Vec<PlayerLaserSerializedData>)), // player_uuid - vec<PlayerLaserSerializedData>
+    LasersShot((String, Option<u64>, Vec<PlayerLaserSerializedData>)), // player_uuid - seq - vec<PlayerLaserSerializedData>
+    ScreenWidthAdjusted(f32),
+    ScreenHeightAdjusted(f32),
+    PlayersSnapshot(Vec<RemoteGamePlayerState>), // all the active players
+    CollectiblesSnapshot(Vec<RemoteCollectibleState>), // all the collectibles
+}
+
+impl RemoteStateType {
+    /// The entity stream an update belongs to and its sequence number in it, for the updates
+    /// carrying one. The events of a player, of its lasers and of a token are separate streams.
+    pub fn sequence(&self) -> Option<(String, u64)> {
+        match self {
+            RemoteStateType::PlayerAdded(player)
+            | RemoteStateType::PlayerRemoved(player)
+            | RemoteStateType::PlayerMoved(player) => {
+                player.seq.map(|seq| (player_stream(&player.uuid), seq))
+            }
+            RemoteStateType::TokenAdded(collectible)
+            | RemoteStateType::TokenRemoved(collectible) => collectible
+                .seq
+                .map(|seq| (token_stream(&collectible.uuid), seq)),
+            RemoteStateType::TokenCollected(collected) => collected
+                .seq
+                .map(|seq| (token_stream(&collected.uuid), seq)),
+            RemoteStateType::LasersShot((player_uuid, seq, _)) => {
+                seq.map(|seq| (format!("lasers/{}", player_uuid), seq))
+            }
+            RemoteStateType::ScreenWidthAdjusted(_)
+            | RemoteStateType::ScreenHeightAdjusted(_)
+            | RemoteStateType::PlayersSnapshot(_)
+            | RemoteStateType::CollectiblesSnapshot(_) => None,
+        }
+    }
+}
+
+/// Sequence stream of the updates of a player
+pub fn player_stream(uuid: &str) -> String {
+    format!("player/{}", uuid)
+}
+
+/// Sequence stream of the updates of a collectible
+pub fn token_stream(uuid: &str) -> String {
+    format!("token/{}", uuid)
 }
 
 #[derive(Clone)]
@@ -88,6 +554,12 @@ pub struct RemoteGameState {
     pub remote_players: BTreeMap<String, RemoteGamePlayerState>, // [player uuid - state mapping]
     pub remote_collectibles: BTreeMap<String, RemoteCollectibleState>, // [player uuid - state mapping]
     pub remote_lasers: BTreeMap<String, BTreeMap<String, RemoteLaserState>>, // [player uuid - [laser uuid: laser state mapping]]
+    pub pending_collectible_claims: BTreeMap<String, PendingCollectibleClaim>, // [collectible uuid - predicted claim]
+    pub applied_sequences: BTreeMap<String, u64>, // [entity stream - last applied sequence number]
+    pub removed_sequences: BTreeMap<String, u64>, // [removed entity stream - last applied sequence number]
+    pub stale_updates: u64, // out-of-order or duplicate updates dropped, since the start
+    pub local_player: Option<RemoteGamePlayerState>, // the local player as added by js
+    pub player_scores: BTreeMap<String, f64>, // [player uuid - value of the collected tokens]
 }
 
 impl RemoteGameState {
@@ -121,6 +593,8 @@ impl RemoteGameState {
         self.remote_players.remove(uuid);
         self.entity_players.remove(uuid);
         self.entity_player_tags.remove(uuid);
+        self.retire_sequence(player_stream(uuid));
+        self.retire_sequence(format!("lasers/{}", uuid));
     }
 
     pub fn clear_remote_players(&mut self) {
@@ -159,6 +633,8 @@ impl RemoteGameState {
     ) {
         self.remote_collectibles.remove(uuid);
         self.entity_collectibles.remove(uuid);
+        self.pending_collectible_claims.remove(uuid);
+        self.retire_sequence(token_stream(uuid));
     }
 
     pub fn get_collectible_entity(
@@ -171,6 +647,47 @@ impl RemoteGameState {
     pub fn clear_collectibles(&mut self) {
         self.remote_collectibles.clear();
     }
+
+    pub fn add_pending_collectible_claim(
+        &mut self,
+        uuid: &str,
+        claim: PendingCollectibleClaim,
+    ) -> Option<PendingCollectibleClaim> {
+        self.pending_collectible_claims
+            .insert(uuid.to_owned(), claim)
+    }
+
+    pub fn has_pending_collectible_claim(
+        &self,
+        uuid: &str,
+    ) -> bool {
+        self.pending_collectible_claims.contains_key(uuid)
+    }
+
+    pub fn take_pending_collectible_claim(
+        &mut self,
+        uuid: &str,
+    ) -> Option<PendingCollectibleClaim> {
+        self.pending_collectible_claims.remove(uuid)
+    }
+
+    /// Removes and returns the uuids of all claims older than `timeout` seconds
+    pub fn take_expired_collectible_claims(
+        &mut self,
+        now: f64,
+        timeout: f64,
+    ) -> Vec<String> {
+        let expired = self
+            .pending_collectible_claims
+            .iter()
+            .filter(|(_, claim)| now - claim.claimed_at > timeout)
+            .map(|(uuid, _)| uuid.clone())
+            .collect::<Vec<String>>();
+        for uuid in expired.iter() {
+            self.pending_collectible_claims.remove(uuid);
+        }
+        expired
+    }
     // ----------------------------------------------
     pub fn add_new_player_tag(
         &mut self,
@@ -186,6 +703,65 @@ impl RemoteGameState {
     ) -> Option<&Entity> {
         self.entity_player_tags.get(uuid)
     }
+
+    // ----------------------------------------------
+    /// Records the sequence number of an update, returns false for an out-of-order or duplicate
+    /// update which must be dropped. The updates without sequence number are always applied.
+    pub fn accept_update_sequence(
+        &mut self,
+        update: &RemoteStateType,
+    ) -> bool {
+        let (stream, seq) = match update.sequence() {
+            Some(sequence) => sequence,
+            None => return true,
+        };
+        match self.last_applied_sequence(&stream) {
+            Some(last_seq) if seq <= last_seq => {
+                self.stale_updates += 1;
+                false
+            }
+            _ => {
+                self.removed_sequences.remove(&stream);
+                self.applied_sequences.insert(stream, seq);
+                true
+            }
+        }
+    }
+
+    /// Last sequence number applied to an entity stream, including the removed entities
+    pub fn last_applied_sequence(
+        &self,
+        stream: &str,
+    ) -> Option<u64> {
+        self.applied_sequences
+            .get(stream)
+            .or_else(|| self.removed_sequences.get(stream))
+            .copied()
+    }
+
+    /// Moves the stream of a removed entity out of the applied sequences, its last sequence is
+    /// kept (up to MAX_REMOVED_SEQUENCES, the oldest are forgotten) so a late update can't revive it.
+    fn retire_sequence(
+        &mut self,
+        stream: String,
+    ) {
+        let seq = match self.applied_sequences.remove(&stream) {
+            Some(seq) => seq,
+            None => return,
+        };
+        self.removed_sequences.insert(stream, seq);
+        while self.removed_sequences.len() > MAX_REMOVED_SEQUENCES {
+            let oldest = self
+                .removed_sequences
+                .iter()
+                .min_by_key(|(_, seq)| **seq)
+                .map(|(stream, _)| stream.clone());
+            match oldest {
+                Some(stream) => self.removed_sequences.remove(&stream),
+                None => break,
+            };
+        }
+    }
 }
 
 impl Default for RemoteGameState {
@@ -198,6 +774,12 @@ impl Default for RemoteGameState {
             remote_players: BTreeMap::new(),
             remote_collectibles: BTreeMap::new(),
             remote_lasers: Default::default(),
+            pending_collectible_claims: BTreeMap::new(),
+            applied_sequences: BTreeMap::new(),
+            removed_sequences: BTreeMap::new(),
+            stale_updates: 0,
+            local_player: None,
+            player_scores: BTreeMap::new(),
         }
     }
 }
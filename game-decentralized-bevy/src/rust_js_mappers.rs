@@ -4,8 +4,9 @@ use wasm_bindgen::{JsCast, JsValue};
 use crate::errors::ClientError;
 use crate::events::{
     CollectedEntityEventData, PlayerLaserEventData, PlayerLaserSerializedData,
-    RemoteCollectibleEventData, RemotePlayerEventData, LASERS_SHOT, PLAYER_ADDED, PLAYER_MOVED,
-    PLAYER_REMOVED, TOKEN_ADDED, TOKEN_COLLECTED, TOKEN_REMOVED,
+    RemoteCollectibleEventData, RemotePlayerEventData, TimedEventData, LASERS_SHOT,
+    PLAYERS_SNAPSHOT, PLAYER_ADDED, PLAYER_MOVED, PLAYER_REMOVED, SCREEN_HEIGHT_ADJUSTED,
+    SCREEN_WIDTH_ADJUSTED, TOKENS_SNAPSHOT, TOKEN_ADDED, TOKEN_COLLECTED, TOKEN_REMOVED,
 };
 use crate::resources::{
     CollectedEntity, EntityType, RemoteCollectibleState, RemoteGamePlayerState, RemoteLaserState,
@@ -91,6 +92,47 @@ pub fn map_type_from_str(r#type: &str) -> EntityType {
 
 // ==============================
 
+/// The operation of an update (e.g. "PLAYER_MOVED"), without decoding its data
+pub fn map_js_update_operation(entity: &GameEntityUpdate) -> Option<String> {
+    get_key_value_from_obj::<String>("operation", entity.as_ref())
+}
+
+/// The chain time (unix milliseconds) of the timestamped updates
+pub fn map_js_update_event_time(entity: &GameEntityUpdate) -> Option<f64> {
+    let js_obj: &JsValue = entity.as_ref();
+    match get_key_value_from_obj::<String>("operation", js_obj)?.as_str() {
+        LASERS_SHOT | TOKEN_COLLECTED | PLAYER_MOVED => {
+            get_key_value_from_obj::<String>("data", js_obj)
+                .and_then(|data| serde_json::from_str::<TimedEventData>(data.as_str()).ok())
+                .map(|data| data.time)
+        }
+        _ => None,
+    }
+}
+
+fn map_player_event_data(data: RemotePlayerEventData) -> RemoteGamePlayerState {
+    RemoteGamePlayerState {
+        uuid: data.uuid,
+        address: data.address,
+        name: data.name,
+        position: Vec3::new(data.x as f32, data.y as f32, 0.0f32),
+        rotation: Quat::from_array([0., 0., data.rot as f32, data.w as f32]),
+        velocity: Vec2::new(data.vx as f32, data.vy as f32),
+        skin: data.skin,
+        r#type: map_type_from_str(&data.r#type),
+        seq: data.seq,
+    }
+}
+
+fn map_collectible_event_data(data: RemoteCollectibleEventData) -> RemoteCollectibleState {
+    RemoteCollectibleState {
+        uuid: data.uuid,
+        position: Vec3::new(data.x as f32, data.y as f32, 0.0f32),
+        kind: data.kind,
+        seq: data.seq,
+    }
+}
+
 pub fn map_js_update_to_rust_entity_state(
     entity: GameEntityUpdate
 ) -> Result<Option<RemoteStateType>, ClientError> {
@@ -103,17 +145,12 @@ pub fn map_js_update_to_rust_entity_state(
     match operation.as_str() {
         PLAYER_ADDED | PLAYER_MOVED | PLAYER_REMOVED => {
             //info!("[BEVY] PLAYER ACTION {:?} ", operation.as_str());
+            // fails on an invalid player address
             let remote_player_event = get_key_value_from_obj::<String>("data", &js_obj)
-                .map(|data| serde_json::from_str::<RemotePlayerEventData>(data.as_str()).ok())
-                .flatten()
-                .map(|data| RemoteGamePlayerState {
-                    uuid: data.uuid,
-                    address: data.address,
-                    name: data.name,
-                    position: Vec3::new(data.x as f32, data.y as f32, 0.0f32),
-                    rotation: Quat::from_array([0., 0., data.rot as f32, data.w as f32]),
-                    r#type: map_type_from_str(&data.r#type),
-                });
+                .map(|data| serde_json::from_str::<RemotePlayerEventData>(data.as_str()))
+                .transpose()
+                .map_err(|err| ClientError::UndecodableEventData(err.to_string()))?
+                .map(map_player_event_data);
 
             match operation.as_str() {
                 PLAYER_ADDED => Ok(remote_player_event.map(RemoteStateType::PlayerAdded)),
@@ -125,12 +162,10 @@ pub fn map_js_update_to_rust_entity_state(
         TOKEN_ADDED | TOKEN_REMOVED => {
             //info!("[BEVY] TOKEN ACTION {:?} ", operation.as_str());
             let collected_entity_event = get_key_value_from_obj::<String>("data", &js_obj)
-                .map(|data| serde_json::from_str::<RemoteCollectibleEventData>(data.as_str()).ok())
-                .flatten()
-                .map(|data| RemoteCollectibleState {
-                    uuid: data.uuid,
-                    position: Vec3::new(data.x as f32, data.y as f32, 0.0f32),
-                });
+                .map(|data| serde_json::from_str::<RemoteCollectibleEventData>(data.as_str()))
+                .transpose()
+                .map_err(|err| ClientError::UndecodableEventData(err.to_string()))?
+                .map(map_collectible_event_data);
 
             match operation.as_str() {
                 TOKEN_ADDED => Ok(collected_entity_event.map(RemoteStateType::TokenAdded)),
@@ -138,47 +173,81 @@ pub fn map_js_update_to_rust_entity_state(
                 _ => Err(ClientError::UnknownOperationReceived),
             }
         }
+        PLAYERS_SNAPSHOT => {
+            // the full list of the active players, as a json array
+            let players = get_key_value_from_obj::<String>("data", &js_obj)
+                .map(|data| serde_json::from_str::<Vec<RemotePlayerEventData>>(data.as_str()))
+                .transpose()
+                .map_err(|err| ClientError::UndecodableEventData(err.to_string()))?
+                .map(|players| players.into_iter().map(map_player_event_data).collect());
+            Ok(players.map(RemoteStateType::PlayersSnapshot))
+        }
+        TOKENS_SNAPSHOT => {
+            // the full list of the collectibles, as a json array
+            let collectibles = get_key_value_from_obj::<String>("data", &js_obj)
+                .map(|data| serde_json::from_str::<Vec<RemoteCollectibleEventData>>(data.as_str()))
+                .transpose()
+                .map_err(|err| ClientError::UndecodableEventData(err.to_string()))?
+                .map(|collectibles| {
+                    collectibles
+                        .into_iter()
+                        .map(map_collectible_event_data)
+                        .collect()
+                });
+            Ok(collectibles.map(RemoteStateType::CollectiblesSnapshot))
+        }
         TOKEN_COLLECTED => {
             let collected_entity_event = get_key_value_from_obj::<String>("data", &js_obj)
-                .map(|data| serde_json::from_str::<CollectedEntityEventData>(data.as_str()).ok())
-                .flatten()
+                .map(|data| serde_json::from_str::<CollectedEntityEventData>(data.as_str()))
+                .transpose()
+                .map_err(|err| ClientError::UndecodableEventData(err.to_string()))?
                 .map(|data| CollectedEntity {
                     uuid: data.uuid,
                     player_uuid: data.player_uuid,
                     value: data.value,
                     time: data.time,
+                    seq: data.seq,
                 });
 
             //info!("[BEVY] TOKEN COLLECTED {:?}", &collected_entity_event);
             return Ok(collected_entity_event.map(RemoteStateType::TokenCollected));
         }
         LASERS_SHOT => {
-            let lasers_shot_event = get_key_value_from_obj::<String>("data", &js_obj)
-                .map(|data| serde_json::from_str::<PlayerLaserEventData>(data.as_str()).ok())
-                .flatten();
-
-            let player_uuid = lasers_shot_event
-                .as_ref()
-                .and_then(|data| Some(data.player_uuid.clone()))
-                .expect("A valid player uuid");
+            // fails on an invalid player address, an update without data maps to nothing
+            let lasers_shot_event = match get_key_value_from_obj::<String>("data", &js_obj)
+                .map(|data| serde_json::from_str::<PlayerLaserEventData>(data.as_str()))
+                .transpose()
+                .map_err(|err| ClientError::UndecodableEventData(err.to_string()))?
+            {
+                Some(lasers_shot_event) => lasers_shot_event,
+                None => return Ok(None),
+            };
 
             let player_lasers = lasers_shot_event
-                .map(|f| {
-                    f.lasers_data
-                        .split("@")
-                        .filter_map(|item| {
-                            serde_json::from_str::<PlayerLaserSerializedData>(item).ok()
-                        })
-                        .collect::<Vec<PlayerLaserSerializedData>>()
-                })
-                .unwrap_or_default();
+                .lasers_data
+                .split("@")
+                .filter_map(|item| serde_json::from_str::<PlayerLaserSerializedData>(item).ok())
+                .collect::<Vec<PlayerLaserSerializedData>>();
 
             //info!("[BEVY] LASERS SHOT EVENT {:?}", &x);
             return Ok(Some(RemoteStateType::LasersShot((
-                player_uuid,
+                lasers_shot_event.player_uuid,
+                lasers_shot_event.seq,
                 player_lasers,
             ))));
         }
-        _ => return Ok(None),
+        SCREEN_WIDTH_ADJUSTED | SCREEN_HEIGHT_ADJUSTED => {
+            // the contract sends the new screen dimension as a plain number string
+            let dimension = get_key_value_from_obj::<String>("data", &js_obj)
+                .and_then(|data| data.trim().parse::<f32>().ok())
+                .filter(|dimension| *dimension > 0.0);
+
+            match operation.as_str() {
+                SCREEN_WIDTH_ADJUSTED => Ok(dimension.map(RemoteStateType::ScreenWidthAdjusted)),
+                SCREEN_HEIGHT_ADJUSTED => Ok(dimension.map(RemoteStateType::ScreenHeightAdjusted)),
+                _ => Err(ClientError::UnknownOperationReceived),
+            }
+        }
+        _ => Err(ClientError::UnknownOperationReceived),
     }
 }
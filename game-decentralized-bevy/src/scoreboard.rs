@@ -0,0 +1,115 @@
+//! Scoreboard: the players ranked by the value of their collected tokens, toggled by the scoreboard
+//! input action and always shown while spectating.
+use bevy::prelude::*;
+
+use crate::components::ScoreboardText;
+use crate::resources::{InputActions, RemoteGameState, Scoreboard};
+use crate::states::GameState;
+use crate::FONT;
+
+// players listed by the scoreboard, the best ones
+const SCOREBOARD_MAX_PLAYERS: usize = 10;
+
+pub struct ScoreboardPlugin;
+
+impl Plugin for ScoreboardPlugin {
+    fn build(
+        &self,
+        app: &mut App,
+    ) {
+        app.init_resource::<Scoreboard>();
+        app.add_startup_system(spawn_scoreboard_system);
+        app.add_system(scoreboard_toggle_system);
+        app.add_system(scoreboard_system);
+    }
+}
+
+fn spawn_scoreboard_system(
+    mut commands: Commands,
+    asset_server: Res<AssetServer>,
+) {
+    commands
+        .spawn_bundle(
+            TextBundle::from_section(
+                "",
+                TextStyle {
+                    font: asset_server.load(FONT),
+                    font_size: 16.0,
+                    color: Color::PINK,
+                },
+            )
+            .with_style(Style {
+                position_type: PositionType::Absolute,
+                position: UiRect {
+                    top: Val::Px(10.0),
+                    left: Val::Px(10.0),
+                    ..default()
+                },
+                ..default()
+            }),
+        )
+        .insert(Visibility { is_visible: false })
+        .insert(ScoreboardText);
+}
+
+fn scoreboard_toggle_system(
+    input_actions: Res<InputActions>,
+    mut scoreboard: ResMut<Scoreboard>,
+) {
+    if input_actions.toggle_scoreboard {
+        scoreboard.visible = !scoreboard.visible;
+    }
+}
+
+/// Lists the local and remote players by score while the scoreboard is shown
+fn scoreboard_system(
+    scoreboard: Res<Scoreboard>,
+    state: Res<State<GameState>>,
+    game_state: Res<RemoteGameState>,
+    mut texts_query: Query<(&mut Text, &mut Visibility), With<ScoreboardText>>,
+) {
+    let visible = scoreboard.visible || state.current() == &GameState::Spectating;
+    for (mut text, mut visibility) in texts_query.iter_mut() {
+        visibility.is_visible = visible;
+        if !visible {
+            continue;
+        }
+
+        let mut scores = game_state
+            .local_player
+            .iter()
+            .chain(game_state.remote_players.values())
+            .map(|player| {
+                let name = if player.name.is_empty() {
+                    player.address.short()
+                } else {
+                    player.name.clone()
+                };
+                let score = game_state
+                    .player_scores
+                    .get(&player.uuid)
+                    .copied()
+                    .unwrap_or_default();
+                (name, score)
+            })
+            .collect::<Vec<(String, f64)>>();
+        scores.sort_by(|(_, a), (_, b)| b.total_cmp(a));
+
+        let lines = scores
+            .iter()
+            .take(SCOREBOARD_MAX_PLAYERS)
+            .enumerate()
+            .map(|(rank, (name, score))| format!("{}. {} {:.0}", rank + 1, name, score))
+            .collect::<Vec<String>>();
+        let value = if lines.is_empty() {
+            "Scoreboard: no players".to_string()
+        } else {
+            format!("Scoreboard\n{}", lines.join("\n"))
+        };
+        if let Some(section) = text.sections.first_mut() {
+            if section.value != value {
+                section.value = value;
+            }
+        }
+    }
+}
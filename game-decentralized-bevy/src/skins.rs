@@ -0,0 +1,76 @@
+//! Ship skins: a hull sprite (one of `PLAYER_SPRITES`) and a tint. Players get a skin derived
+//! from their address unless they picked one of the named skins, so every client renders a given
+//! player the same way.
+use bevy::prelude::*;
+
+use crate::PLAYER_SPRITES;
+
+/// Named skins a player can pick: (skin name, hull name, tint)
+const NAMED_SKINS: [(&str, &str, Color); 8] = [
+    ("classic", "classic", Color::WHITE),
+    ("raider", "raider", Color::WHITE),
+    ("phantom", "phantom", Color::rgb(0.6, 0.8, 1.0)),
+    ("wraith", "wraith", Color::rgb(0.8, 0.55, 1.0)),
+    ("ember", "phantom", Color::rgb(1.0, 0.55, 0.3)),
+    ("venom", "wraith", Color::rgb(0.45, 1.0, 0.45)),
+    ("frost", "raider", Color::rgb(0.6, 1.0, 1.0)),
+    ("gold", "classic", Color::rgb(1.0, 0.85, 0.35)),
+];
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct PlayerSkin {
+    /// key of the hull sprite in `GameTextures.player`
+    pub hull: &'static str,
+    pub tint: Color,
+}
+
+impl PlayerSkin {
+    /// The named skin picked by the player if known, the address-derived one otherwise
+    pub fn for_player(
+        address: &str,
+        skin: Option<&str>,
+    ) -> Self {
+        skin.and_then(PlayerSkin::from_name)
+            .unwrap_or_else(|| PlayerSkin::from_address(address))
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        NAMED_SKINS
+            .iter()
+            .find(|(skin_name, ..)| skin_name.eq_ignore_ascii_case(name))
+            .map(|(_, hull, tint)| PlayerSkin { hull, tint: *tint })
+    }
+
+    /// Deterministically picks a hull and a light tint (so the hull details stay visible) from
+    /// the address
+    pub fn from_address(address: &str) -> Self {
+        let hash = fnv1a(address.as_bytes());
+        let (hull, _) = PLAYER_SPRITES[(hash % PLAYER_SPRITES.len() as u64) as usize];
+        let hue = ((hash >> 16) % 360) as f32;
+        PlayerSkin {
+            hull,
+            tint: Color::hsl(hue, 0.7, 0.75),
+        }
+    }
+
+    /// Colour of the player name tag, matching the ship
+    pub fn tag_color(&self) -> Color {
+        if self.tint == Color::WHITE {
+            Color::GREEN
+        } else {
+            self.tint
+        }
+    }
+}
+
+/// Names of the skins players can pick
+pub fn skin_names() -> Vec<&'static str> {
+    NAMED_SKINS.iter().map(|(name, ..)| *name).collect()
+}
+
+// 64-bit FNV-1a, stable across platforms and releases (unlike the std hasher)
+fn fnv1a(bytes: &[u8]) -> u64 {
+    bytes.iter().fold(0xcbf29ce484222325, |hash, byte| {
+        (hash ^ *byte as u64).wrapping_mul(0x100000001b3)
+    })
+}
@@ -0,0 +1,612 @@
+//! Game snapshots: the whole [`RemoteGameState`] along with the live entity transforms, published
+//! to js every second (for bug reports and debugging) and importable from js to bootstrap a
+//! late-joining client. An import clears the remote world and replays the snapshot as game entity
+//! updates through the inbox, so the entities are spawned exactly like from chain events.
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+use crate::address::MassaAddress;
+use crate::components::{Health, LocalLaser, RemoteLaser, Velocity};
+use crate::diagnostics::unix_time_millis;
+use crate::events::{
+    PlayerLaserEventData, PlayerLaserSerializedData, RemoteCollectibleEventData,
+    RemotePlayerEventData, LASERS_SHOT, PLAYER_ADDED, TOKEN_ADDED,
+};
+use crate::powerups::CollectibleKind;
+use crate::resources::{
+    EntityType, MovementValidation, PendingCollectibleClaim, RemoteGamePlayerState, RemoteGameState,
+};
+use crate::wasm::{GameEntityUpdate, GAME_ENTITY_UPDATE, GAME_SNAPSHOT, GAME_SNAPSHOT_IMPORTS};
+use crate::weapons::WeaponKind;
+
+pub const SNAPSHOT_VERSION: u32 = 1;
+// seconds between two snapshot refreshes
+const SNAPSHOT_REFRESH_INTERVAL: f32 = 1.0;
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct GameSnapshot {
+    pub version: u32,
+    /// unix time of the export, in milliseconds
+    pub time: f64,
+    pub players: Vec<PlayerSnapshot>,
+    pub collectibles: Vec<CollectibleSnapshot>,
+    pub lasers: Vec<LaserSnapshot>,
+    /// value of the collected tokens, by player uuid
+    #[serde(default)]
+    pub scores: BTreeMap<String, f64>,
+    /// predicted collector, by collectible uuid
+    #[serde(default)]
+    pub pending_collectible_claims: BTreeMap<String, String>,
+    /// last applied sequence number, by entity stream
+    #[serde(default)]
+    pub applied_sequences: BTreeMap<String, u64>,
+}
+
+/// A position, rotation (z and w of the quaternion) and linear velocity
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TransformSnapshot {
+    pub x: f32,
+    pub y: f32,
+    pub rot: f32,
+    pub w: f32,
+    #[serde(default)]
+    pub vx: f32,
+    #[serde(default)]
+    pub vy: f32,
+}
+
+impl TransformSnapshot {
+    fn new(
+        translation: Vec3,
+        rotation: Quat,
+        velocity: Vec2,
+    ) -> Self {
+        Self {
+            x: translation.x,
+            y: translation.y,
+            rot: rotation.z,
+            w: rotation.w,
+            vx: velocity.x,
+            vy: velocity.y,
+        }
+    }
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PlayerSnapshot {
+    pub uuid: String,
+    pub address: MassaAddress,
+    pub name: String,
+    #[serde(default)]
+    pub skin: Option<String>,
+    /// "local" or "remote", from the point of view of the exporting client
+    pub r#type: String,
+    /// last state received from the chain
+    pub chain: Option<TransformSnapshot>,
+    /// rendered state
+    #[serde(default)]
+    pub live: Option<TransformSnapshot>,
+    #[serde(default)]
+    pub health: Option<f32>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CollectibleSnapshot {
+    pub uuid: String,
+    pub kind: CollectibleKind,
+    pub x: f32,
+    pub y: f32,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct LaserSnapshot {
+    pub uuid: String,
+    pub player_uuid: String,
+    pub weapon: WeaponKind,
+    /// shot by the exporting client
+    pub local: bool,
+    /// state received from the chain (remote lasers only)
+    #[serde(default)]
+    pub chain: Option<TransformSnapshot>,
+    /// rendered state
+    #[serde(default)]
+    pub live: Option<TransformSnapshot>,
+}
+
+pub struct GameSnapshotPlugin;
+
+impl Plugin for GameSnapshotPlugin {
+    fn build(
+        &self,
+        app: &mut App,
+    ) {
+        app.add_system(snapshot_import_system);
+        // after the gameplay systems, to see the entities spawned and despawned this frame
+        app.add_system_to_stage(CoreStage::PostUpdate, snapshot_export_system);
+    }
+}
+
+fn player_snapshot(
+    player: &RemoteGamePlayerState,
+    chain: Option<TransformSnapshot>,
+) -> PlayerSnapshot {
+    PlayerSnapshot {
+        uuid: player.uuid.clone(),
+        address: player.address.clone(),
+        name: player.name.clone(),
+        skin: player.skin.clone(),
+        r#type: match player.r#type {
+            EntityType::Local => "local".to_string(),
+            EntityType::Remote => "remote".to_string(),
+        },
+        chain,
+        live: None,
+        health: None,
+    }
+}
+
+/// Builds the snapshot of the game state and the live entities
+fn build_snapshot(
+    game_state: &RemoteGameState,
+    players_query: &Query<(&Transform, Option<&Velocity>, Option<&Health>)>,
+    local_lasers_query: &Query<(&Transform, &LocalLaser)>,
+    remote_lasers_query: &Query<(&Transform, &RemoteLaser)>,
+) -> GameSnapshot {
+    let mut players = game_state
+        .local_player
+        .iter()
+        .map(|local_player| player_snapshot(local_player, None))
+        .chain(game_state.remote_players.values().map(|remote_player| {
+            let chain = TransformSnapshot::new(
+                remote_player.position,
+                remote_player.rotation,
+                remote_player.velocity,
+            );
+            player_snapshot(remote_player, Some(chain))
+        }))
+        .collect::<Vec<PlayerSnapshot>>();
+    for player in players.iter_mut() {
+        let live = game_state
+            .entity_players
+            .get(&player.uuid)
+            .and_then(|entity| players_query.get(*entity).ok());
+        if let Some((transform, velocity, health)) = live {
+            let velocity = velocity.map(|velocity| velocity.vector).unwrap_or_default();
+            player.live = Some(TransformSnapshot::new(
+                transform.translation,
+                transform.rotation,
+                velocity,
+            ));
+            player.health = health.map(|health| health.current);
+        }
+    }
+
+    let collectibles = game_state
+        .remote_collectibles
+        .values()
+        .map(|collectible| CollectibleSnapshot {
+            uuid: collectible.uuid.clone(),
+            kind: collectible.kind,
+            x: collectible.position.x,
+            y: collectible.position.y,
+        })
+        .collect::<Vec<CollectibleSnapshot>>();
+
+    let mut lasers = local_lasers_query
+        .iter()
+        .map(|(transform, local_laser)| (transform, &local_laser.0, true))
+        .chain(
+            remote_lasers_query
+                .iter()
+                .map(|(transform, remote_laser)| (transform, &remote_laser.0, false)),
+        )
+        .map(|(transform, laser, local)| LaserSnapshot {
+            uuid: laser.uuid.to_string(),
+            player_uuid: laser.player_uuid.clone(),
+            weapon: laser.weapon,
+            local,
+            chain: None,
+            live: Some(TransformSnapshot::new(
+                transform.translation,
+                transform.rotation,
+                Vec2::ZERO,
+            )),
+        })
+        .collect::<Vec<LaserSnapshot>>();
+    for laser_state in game_state
+        .remote_lasers
+        .values()
+        .flat_map(|lasers| lasers.values())
+    {
+        let chain = TransformSnapshot {
+            x: laser_state.x as f32,
+            y: laser_state.y as f32,
+            rot: laser_state.rot as f32,
+            w: laser_state.w as f32,
+            vx: 0.0,
+            vy: 0.0,
+        };
+        match lasers
+            .iter_mut()
+            .find(|laser| laser.uuid.eq(&laser_state.uuid))
+        {
+            Some(laser) => laser.chain = Some(chain),
+            None => lasers.push(LaserSnapshot {
+                uuid: laser_state.uuid.clone(),
+                player_uuid: laser_state.player_uuid.clone(),
+                weapon: laser_state.weapon,
+                local: false,
+                chain: Some(chain),
+                live: None,
+            }),
+        }
+    }
+
+    GameSnapshot {
+        version: SNAPSHOT_VERSION,
+        time: unix_time_millis(),
+        players,
+        collectibles,
+        lasers,
+        scores: game_state.player_scores.clone(),
+        pending_collectible_claims: game_state
+            .pending_collectible_claims
+            .iter()
+            .map(|(uuid, claim)| (uuid.clone(), claim.player_uuid.clone()))
+            .collect(),
+        applied_sequences: game_state.applied_sequences.clone(),
+    }
+}
+
+fn snapshot_export_system(
+    time: Res<Time>,
+    mut since_refresh: Local<f32>,
+    game_state: Res<RemoteGameState>,
+    players_query: Query<(&Transform, Option<&Velocity>, Option<&Health>)>,
+    local_lasers_query: Query<(&Transform, &LocalLaser)>,
+    remote_lasers_query: Query<(&Transform, &RemoteLaser)>,
+) {
+    *since_refresh += time.delta_seconds();
+    if *since_refresh < SNAPSHOT_REFRESH_INTERVAL {
+        return;
+    }
+    *since_refresh = 0.0;
+
+    let snapshot = build_snapshot(
+        &game_state,
+        &players_query,
+        &local_lasers_query,
+        &remote_lasers_query,
+    );
+    let serialized_snapshot = serde_json::to_string(&snapshot).unwrap_or_default();
+    GAME_SNAPSHOT.with(|published| *published.borrow_mut() = serialized_snapshot);
+}
+
+/// The updates replaying a snapshot: the remote players (the local player of the snapshot
+/// included, unless it is our own), their lasers and the collectibles
+fn snapshot_updates(
+    snapshot: &GameSnapshot,
+    local_player_uuid: Option<&str>,
+) -> Vec<(&'static str, String)> {
+    let mut updates = vec![];
+    for player in snapshot.players.iter() {
+        if Some(player.uuid.as_str()) == local_player_uuid {
+            continue;
+        }
+        let state = match player.chain.as_ref().or(player.live.as_ref()) {
+            Some(state) => state,
+            None => continue,
+        };
+        let event = RemotePlayerEventData {
+            uuid: player.uuid.clone(),
+            address: player.address.clone(),
+            name: player.name.clone(),
+            x: state.x as f64,
+            y: state.y as f64,
+            rot: state.rot as f64,
+            w: state.w as f64,
+            vx: state.vx as f64,
+            vy: state.vy as f64,
+            skin: player.skin.clone(),
+            r#type: "remote".to_string(),
+            seq: None,
+        };
+        updates.push((
+            PLAYER_ADDED,
+            serde_json::to_string(&event).unwrap_or_default(),
+        ));
+
+        let lasers_data = snapshot
+            .lasers
+            .iter()
+            .filter(|laser| laser.player_uuid.eq(&player.uuid))
+            .filter_map(|laser| {
+                let state = laser.chain.as_ref().or(laser.live.as_ref())?;
+                let laser_data = PlayerLaserSerializedData {
+                    player_uuid: laser.player_uuid.clone(),
+                    uuid: laser.uuid.clone(),
+                    x: state.x as f64,
+                    y: state.y as f64,
+                    rot: state.rot as f64,
+                    w: state.w as f64,
+                    weapon: laser.weapon,
+                    start_x: None,
+                    start_y: None,
+                    age: 0.0,
+                };
+                serde_json::to_string(&laser_data).ok()
+            })
+            .collect::<Vec<String>>();
+        if !lasers_data.is_empty() {
+            let event = PlayerLaserEventData {
+                player_address: player.address.clone(),
+                player_uuid: player.uuid.clone(),
+                lasers_data: lasers_data.join("@"),
+                time: snapshot.time,
+                seq: None,
+            };
+            updates.push((
+                LASERS_SHOT,
+                serde_json::to_string(&event).unwrap_or_default(),
+            ));
+        }
+    }
+
+    for collectible in snapshot.collectibles.iter() {
+        let event = RemoteCollectibleEventData {
+            uuid: collectible.uuid.clone(),
+            x: collectible.x as f64,
+            y: collectible.y as f64,
+            kind: collectible.kind,
+            seq: None,
+        };
+        updates.push((
+            TOKEN_ADDED,
+            serde_json::to_string(&event).unwrap_or_default(),
+        ));
+    }
+    updates
+}
+
+/// Restores the state which is not carried by the replayed updates
+fn restore_snapshot_state(
+    game_state: &mut RemoteGameState,
+    snapshot: &GameSnapshot,
+    now: f64,
+) {
+    game_state.pending_collectible_claims = snapshot
+        .pending_collectible_claims
+        .iter()
+        .map(|(uuid, player_uuid)| {
+            let claim = PendingCollectibleClaim {
+                player_uuid: player_uuid.clone(),
+                claimed_at: now,
+            };
+            (uuid.clone(), claim)
+        })
+        .collect();
+    game_state.player_scores = snapshot.scores.clone();
+    game_state.applied_sequences = snapshot.applied_sequences.clone();
+    game_state.removed_sequences.clear();
+}
+
+/// Replaces the remote world by the snapshots imported from js. The local player is kept.
+fn snapshot_import_system(
+    time: Res<Time>,
+    mut commands: Commands,
+    mut game_state: ResMut<RemoteGameState>,
+    mut movement_validation: ResMut<MovementValidation>,
+    remote_lasers_query: Query<Entity, With<RemoteLaser>>,
+) {
+    let imports = GAME_SNAPSHOT_IMPORTS.with(|imports| imports.take());
+    for snapshot in imports.into_iter() {
+        let local_player_uuid = game_state
+            .local_player
+            .as_ref()
+            .map(|local_player| local_player.uuid.clone());
+
+        // despawn the remote entities, along with the name tags
+        let remote_players = game_state
+            .entity_players
+            .iter()
+            .filter(|(uuid, _)| Some(*uuid) != local_player_uuid.as_ref())
+            .map(|(uuid, entity)| (uuid.clone(), *entity))
+            .collect::<Vec<(String, Entity)>>();
+        for (uuid, entity) in remote_players.iter() {
+            commands.entity(*entity).despawn_recursive();
+            game_state.remove_remote_player(uuid);
+        }
+        game_state.clear_remote_players();
+        // the name tags are children of the players, only their entries are left
+        game_state
+            .entity_player_tags
+            .retain(|uuid, _| Some(uuid) == local_player_uuid.as_ref());
+        // the plausibility records would check the imported players against the old moves
+        movement_validation.players.clear();
+        for entity in game_state.entity_collectibles.values() {
+            commands.entity(*entity).despawn();
+        }
+        game_state.entity_collectibles.clear();
+        game_state.clear_collectibles();
+        for entity in remote_lasers_query.iter() {
+            commands.entity(entity).despawn();
+        }
+        game_state.remote_lasers.clear();
+        // the local lasers are kept, along with their entries
+        game_state
+            .entity_lasers
+            .retain(|uuid, _| Some(uuid) == local_player_uuid.as_ref());
+
+        restore_snapshot_state(&mut game_state, &snapshot, time.seconds_since_startup());
+
+        let updates = snapshot_updates(&snapshot, local_player_uuid.as_deref());
+        info!(
+            "Importing a game snapshot: {:?} players, {:?} collectibles, {:?} updates",
+            snapshot.players.len(),
+            snapshot.collectibles.len(),
+            updates.len()
+        );
+        GAME_ENTITY_UPDATE.with(|inbox| {
+            let mut inbox = inbox.borrow_mut();
+            for (operation, data) in updates.into_iter() {
+                inbox.push(GameEntityUpdate::new_player(operation.to_string(), data));
+            }
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const LOCAL_ADDRESS: &str = "AU1SkB92YpWm4Q2ijQHH34cqbKkCZWszsiQgHVjtNeFF2Ds6STz";
+    const REMOTE_ADDRESS: &str = "AU1Vjx4xjGnySHxfqazRQJYFzpRjA3rgcNEBcAasrQq9es4ZCcC6";
+
+    fn transform(
+        x: f32,
+        y: f32,
+    ) -> TransformSnapshot {
+        TransformSnapshot {
+            x,
+            y,
+            rot: 0.0,
+            w: 1.0,
+            vx: 2.0,
+            vy: -2.0,
+        }
+    }
+
+    fn exported_snapshot() -> GameSnapshot {
+        GameSnapshot {
+            version: SNAPSHOT_VERSION,
+            time: 1_000.0,
+            players: vec![
+                PlayerSnapshot {
+                    uuid: "local".to_string(),
+                    address: LOCAL_ADDRESS.parse().unwrap(),
+                    name: "local".to_string(),
+                    skin: None,
+                    r#type: "local".to_string(),
+                    chain: None,
+                    live: Some(transform(1.0, 1.0)),
+                    health: Some(100.0),
+                },
+                PlayerSnapshot {
+                    uuid: "remote".to_string(),
+                    address: REMOTE_ADDRESS.parse().unwrap(),
+                    name: "remote".to_string(),
+                    skin: Some("stealth".to_string()),
+                    r#type: "remote".to_string(),
+                    chain: Some(transform(10.0, 20.0)),
+                    live: Some(transform(11.0, 21.0)),
+                    health: None,
+                },
+            ],
+            collectibles: vec![CollectibleSnapshot {
+                uuid: "token".to_string(),
+                kind: CollectibleKind::GoldToken,
+                x: 30.0,
+                y: 40.0,
+            }],
+            lasers: vec![LaserSnapshot {
+                uuid: "laser".to_string(),
+                player_uuid: "remote".to_string(),
+                weapon: WeaponKind::SpreadShot,
+                local: false,
+                chain: None,
+                live: Some(transform(50.0, 60.0)),
+            }],
+            scores: BTreeMap::from([("remote".to_string(), 12.0)]),
+            pending_collectible_claims: BTreeMap::from([(
+                "token".to_string(),
+                "local".to_string(),
+            )]),
+            applied_sequences: BTreeMap::from([
+                ("player/remote".to_string(), 7),
+                ("token/token".to_string(), 3),
+            ]),
+        }
+    }
+
+    // through json, as exported to and imported from js
+    fn round_trip(snapshot: &GameSnapshot) -> GameSnapshot {
+        let exported = serde_json::to_string(snapshot).unwrap();
+        serde_json::from_str(&exported).unwrap()
+    }
+
+    #[test]
+    fn an_imported_snapshot_replays_the_remote_world() {
+        let snapshot = round_trip(&exported_snapshot());
+        let updates = snapshot_updates(&snapshot, Some("other"));
+
+        let operations = updates
+            .iter()
+            .map(|(operation, _)| *operation)
+            .collect::<Vec<&str>>();
+        assert_eq!(
+            operations,
+            vec![PLAYER_ADDED, PLAYER_ADDED, LASERS_SHOT, TOKEN_ADDED]
+        );
+
+        // the players are replayed as remote ones, from the chain state when known
+        let local = serde_json::from_str::<RemotePlayerEventData>(&updates[0].1).unwrap();
+        assert_eq!((local.uuid.as_str(), local.x, local.y), ("local", 1.0, 1.0));
+        assert_eq!(local.r#type, "remote");
+        let remote = serde_json::from_str::<RemotePlayerEventData>(&updates[1].1).unwrap();
+        assert_eq!(remote.address.as_str(), REMOTE_ADDRESS);
+        assert_eq!((remote.x, remote.y, remote.vx), (10.0, 20.0, 2.0));
+        assert_eq!(remote.skin.as_deref(), Some("stealth"));
+        assert_eq!(remote.seq, None);
+
+        let lasers = serde_json::from_str::<PlayerLaserEventData>(&updates[2].1).unwrap();
+        assert_eq!(lasers.player_uuid, "remote");
+        assert_eq!(lasers.time, 1_000.0);
+        let laser = serde_json::from_str::<PlayerLaserSerializedData>(&lasers.lasers_data).unwrap();
+        assert_eq!(
+            (laser.uuid.as_str(), laser.x, laser.y),
+            ("laser", 50.0, 60.0)
+        );
+        assert_eq!(laser.weapon, WeaponKind::SpreadShot);
+
+        let token = serde_json::from_str::<RemoteCollectibleEventData>(&updates[3].1).unwrap();
+        assert_eq!(
+            (token.uuid.as_str(), token.x, token.y),
+            ("token", 30.0, 40.0)
+        );
+        assert_eq!(token.kind, CollectibleKind::GoldToken);
+    }
+
+    #[test]
+    fn our_own_player_is_not_replayed() {
+        let snapshot = round_trip(&exported_snapshot());
+        let updates = snapshot_updates(&snapshot, Some("local"));
+        assert_eq!(updates.len(), 3);
+        let remote = serde_json::from_str::<RemotePlayerEventData>(&updates[0].1).unwrap();
+        assert_eq!(remote.uuid, "remote");
+    }
+
+    #[test]
+    fn an_imported_snapshot_restores_the_sequences_scores_and_claims() {
+        let snapshot = round_trip(&exported_snapshot());
+        let mut game_state = RemoteGameState::default();
+        game_state
+            .applied_sequences
+            .insert("player/gone".to_string(), 99);
+
+        restore_snapshot_state(&mut game_state, &snapshot, 5.0);
+
+        assert_eq!(game_state.applied_sequences, snapshot.applied_sequences);
+        assert_eq!(game_state.player_scores, snapshot.scores);
+        let claim = &game_state.pending_collectible_claims["token"];
+        assert_eq!(
+            (claim.player_uuid.as_str(), claim.claimed_at),
+            ("local", 5.0)
+        );
+    }
+}
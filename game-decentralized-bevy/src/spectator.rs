@@ -0,0 +1,117 @@
+//! Spectator mode: the remote players, tokens and lasers are shown without a local player, e.g.
+//! for streaming or before registering. The local input drives the camera instead of a ship: the
+//! movement actions pan the free camera, fire cycles the followed player and switching weapon
+//! frees the camera. The outbound player state is held while spectating.
+use bevy::prelude::*;
+
+use crate::components::RemotePlayer;
+use crate::resources::{InputActions, RemoteGameState, SpectatorCamera};
+use crate::states::GameState;
+use crate::wasm::{Position, LOCAL_PLAYER_LASERS, LOCAL_PLAYER_POSITION, SPECTATING};
+use crate::BOUNDS;
+
+// speed of the free camera, in units per second
+const SPECTATOR_CAMERA_SPEED: f32 = 400.0;
+
+pub struct SpectatorPlugin;
+
+impl Plugin for SpectatorPlugin {
+    fn build(
+        &self,
+        app: &mut App,
+    ) {
+        app.init_resource::<SpectatorCamera>();
+        app.add_system_set(
+            SystemSet::on_enter(GameState::Spectating).with_system(enter_spectator_system),
+        );
+        app.add_system_set(
+            SystemSet::on_update(GameState::Spectating).with_system(spectator_camera_system),
+        );
+        app.add_system_set(
+            SystemSet::on_exit(GameState::Spectating).with_system(exit_spectator_system),
+        );
+    }
+}
+
+/// Frees the camera and clears the outbound player state, which js would otherwise send on chain
+fn enter_spectator_system(mut spectator_camera: ResMut<SpectatorCamera>) {
+    spectator_camera.followed = None;
+    SPECTATING.with(|spectating| *spectating.borrow_mut() = true);
+    LOCAL_PLAYER_POSITION.with(|position| {
+        *position.borrow_mut() = Position {
+            x: 0.0,
+            y: 0.0,
+            rot: 0.0,
+            w: 0.0,
+            vx: 0.0,
+            vy: 0.0,
+        };
+    });
+    LOCAL_PLAYER_LASERS.with(|lasers| *lasers.borrow_mut() = None);
+}
+
+/// Centers the camera back on the screen for the local player
+fn exit_spectator_system(mut camera_query: Query<&mut Transform, With<Camera2d>>) {
+    SPECTATING.with(|spectating| *spectating.borrow_mut() = false);
+    for mut camera_transform in camera_query.iter_mut() {
+        camera_transform.translation.x = 0.0;
+        camera_transform.translation.y = 0.0;
+    }
+}
+
+fn spectator_camera_system(
+    time: Res<Time>,
+    mut input_actions: ResMut<InputActions>,
+    mut spectator_camera: ResMut<SpectatorCamera>,
+    game_state: Res<RemoteGameState>,
+    players_query: Query<(&RemotePlayer, &Transform), Without<Camera2d>>,
+    mut camera_query: Query<&mut Transform, With<Camera2d>>,
+) {
+    // the pending shot and weapon switch are spectator commands, there is no ship to consume them
+    let cycle = std::mem::take(&mut input_actions.fire);
+    let free = std::mem::take(&mut input_actions.switch_weapon);
+
+    // forget the followed player once it left
+    let followed_left = spectator_camera
+        .followed
+        .as_ref()
+        .map_or(false, |uuid| !game_state.remote_players.contains_key(uuid));
+    if free || followed_left {
+        spectator_camera.followed = None;
+    }
+    if cycle && !free {
+        // the next player by uuid, back to the free camera after the last one
+        spectator_camera.followed = match spectator_camera.followed.as_ref() {
+            Some(uuid) => game_state
+                .remote_players
+                .range::<String, _>((std::ops::Bound::Excluded(uuid), std::ops::Bound::Unbounded))
+                .next()
+                .map(|(uuid, _)| uuid.clone()),
+            None => game_state.remote_players.keys().next().cloned(),
+        };
+    }
+
+    let mut camera_transform = match camera_query.get_single_mut() {
+        Ok(camera_transform) => camera_transform,
+        Err(_) => return,
+    };
+    let followed_position = spectator_camera.followed.as_ref().and_then(|uuid| {
+        players_query
+            .iter()
+            .find(|(remote_player, _)| remote_player.0.eq(uuid))
+            .map(|(_, transform)| transform.translation.truncate())
+    });
+    let position = match followed_position {
+        Some(position) => position,
+        None => {
+            // rotate left/right pans horizontally, thrust/brake pans vertically
+            let brake = if input_actions.brake { 1.0 } else { 0.0 };
+            let direction = Vec2::new(-input_actions.rotation, input_actions.thrust - brake);
+            let position = camera_transform.translation.truncate()
+                + direction * SPECTATOR_CAMERA_SPEED * time.delta_seconds();
+            position.clamp(-BOUNDS / 2.0, BOUNDS / 2.0)
+        }
+    };
+    camera_transform.translation.x = position.x;
+    camera_transform.translation.y = position.y;
+}
@@ -0,0 +1,266 @@
+//! Game lifecycle: Loading → WaitingForPlayer (⇄ Spectating) → Playing ⇄ Dead → Disconnected.
+//! Every state but Playing and Spectating shows a screen, the gameplay system sets are gated on the
+//! state, and js can drive the state through `set_game_phase`.
+use bevy::asset::LoadState;
+use bevy::ecs::schedule::ShouldRun;
+use bevy::prelude::*;
+
+use crate::components::{GameStateScreen, GameStateScreenText, LocalPlayer};
+use crate::resources::{GameTextures, PageActivity};
+use crate::wasm::{set_game_ready, GAME_ASSET_ERRORS, GAME_PHASE, GAME_PHASE_REQUESTS};
+use crate::FONT;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum GameState {
+    Loading,
+    WaitingForPlayer,
+    Playing,
+    Dead,
+    Disconnected,
+    /// the world is shown without a local player (see the spectator module)
+    Spectating,
+}
+
+impl GameState {
+    pub const ALL: [GameState; 6] = [
+        GameState::Loading,
+        GameState::WaitingForPlayer,
+        GameState::Playing,
+        GameState::Dead,
+        GameState::Disconnected,
+        GameState::Spectating,
+    ];
+
+    pub fn from_name(name: &str) -> Option<GameState> {
+        GameState::ALL
+            .iter()
+            .find(|state| format!("{:?}", state).eq(name))
+            .copied()
+    }
+
+    /// Text of the state screen, `None` for the states without one
+    pub fn screen_text(&self) -> Option<&'static str> {
+        match self {
+            GameState::Loading => Some("Loading..."),
+            GameState::WaitingForPlayer => Some("Waiting for the player registration..."),
+            GameState::Playing => None,
+            GameState::Dead => Some("Your ship was destroyed"),
+            GameState::Disconnected => Some("Disconnected from the network"),
+            GameState::Spectating => None,
+        }
+    }
+}
+
+pub struct GameStatePlugin;
+
+impl Plugin for GameStatePlugin {
+    fn build(
+        &self,
+        app: &mut App,
+    ) {
+        app.add_state(GameState::Loading);
+        for state in GameState::ALL {
+            if state.screen_text().is_some() {
+                app.add_system_set(
+                    SystemSet::on_enter(state).with_system(spawn_state_screen_system),
+                );
+                app.add_system_set(
+                    SystemSet::on_exit(state).with_system(despawn_state_screen_system),
+                );
+            }
+        }
+        app.add_system_set(SystemSet::on_update(GameState::Loading).with_system(loading_system));
+        app.add_system(game_phase_requests_system);
+        app.add_system(publish_game_phase_system);
+        // after the gameplay systems, to see the local player despawns
+        app.add_system_to_stage(CoreStage::PostUpdate, local_player_state_system);
+    }
+}
+
+/// Run criteria chained to the fixed timestep of the world simulation, which keeps running while
+/// the local player is waiting to join, dead or spectating, but not while the page is hidden
+pub fn world_running(
+    In(fixed_step): In<ShouldRun>,
+    state: Res<State<GameState>>,
+    activity: Res<PageActivity>,
+) -> ShouldRun {
+    if activity.hidden {
+        return ShouldRun::No;
+    }
+    match state.current() {
+        GameState::WaitingForPlayer
+        | GameState::Playing
+        | GameState::Dead
+        | GameState::Spectating => fixed_step,
+        GameState::Loading | GameState::Disconnected => ShouldRun::No,
+    }
+}
+
+/// Run criteria chained to the fixed timestep of the local player systems (inputs, movement,
+/// shooting)
+pub fn local_player_running(
+    In(fixed_step): In<ShouldRun>,
+    state: Res<State<GameState>>,
+    activity: Res<PageActivity>,
+) -> ShouldRun {
+    if activity.hidden {
+        return ShouldRun::No;
+    }
+    match state.current() {
+        GameState::Playing => fixed_step,
+        _ => ShouldRun::No,
+    }
+}
+
+fn set_state(
+    state: &mut State<GameState>,
+    next: GameState,
+) {
+    if state.current() == &next {
+        return;
+    }
+    if let Err(err) = state.set(next) {
+        warn!("Could not switch to the game state {:?}: {:?}", next, err);
+    }
+}
+
+/// Waits for all the game textures and the font to load, showing the progress, then reports the
+/// game as ready. Failed loads are reported to js and keep the game loading.
+fn loading_system(
+    mut state: ResMut<State<GameState>>,
+    mut failures_reported: Local<bool>,
+    asset_server: Res<AssetServer>,
+    game_textures: Res<GameTextures>,
+    mut texts_query: Query<&mut Text, With<GameStateScreenText>>,
+) {
+    let handles = game_textures.handles();
+    let mut loaded = 0;
+    let mut failed = vec![];
+    for handle in handles.iter() {
+        match asset_server.get_load_state(handle) {
+            LoadState::Loaded => loaded += 1,
+            LoadState::Failed => failed.push(
+                asset_server
+                    .get_handle_path(handle)
+                    .map(|path| path.path().display().to_string())
+                    .unwrap_or_default(),
+            ),
+            _ => {}
+        }
+    }
+
+    let progress = if failed.is_empty() {
+        format!("Loading... {}/{}", loaded, handles.len())
+    } else {
+        format!("Failed to load {} game assets", failed.len())
+    };
+    for mut text in texts_query.iter_mut() {
+        if let Some(section) = text.sections.first_mut() {
+            section.value = progress.clone();
+        }
+    }
+
+    if !failed.is_empty() {
+        if !*failures_reported {
+            *failures_reported = true;
+            for path in failed.iter() {
+                error!("Failed to load the asset {:?}", path);
+            }
+            GAME_ASSET_ERRORS.with(|errors| *errors.borrow_mut() = failed);
+        }
+        return;
+    }
+
+    if loaded == handles.len() {
+        set_game_ready();
+        set_state(&mut state, GameState::WaitingForPlayer);
+    }
+}
+
+/// Applies the game phases requested from js. The requests wait in the queue while the game is
+/// loading, so js can't leave the loading phase before the assets are ready.
+fn game_phase_requests_system(mut state: ResMut<State<GameState>>) {
+    if state.current() == &GameState::Loading {
+        return;
+    }
+    let phase_requests = GAME_PHASE_REQUESTS.with(|requests| requests.take());
+    for phase in phase_requests.iter() {
+        match GameState::from_name(phase) {
+            Some(next) => set_state(&mut state, next),
+            None => warn!("Invalid game phase request {:?}", phase),
+        }
+    }
+}
+
+fn publish_game_phase_system(state: Res<State<GameState>>) {
+    if state.is_changed() {
+        GAME_PHASE.with(|phase| {
+            *phase.borrow_mut() = format!("{:?}", state.current());
+        });
+    }
+}
+
+/// Starts playing once the local player is spawned (also when spectating, once registered), and
+/// dies when it gets despawned
+fn local_player_state_system(
+    mut state: ResMut<State<GameState>>,
+    added_local_players: Query<(), Added<LocalPlayer>>,
+    removed_local_players: RemovedComponents<LocalPlayer>,
+) {
+    match state.current() {
+        GameState::WaitingForPlayer | GameState::Dead | GameState::Spectating
+            if !added_local_players.is_empty() =>
+        {
+            set_state(&mut state, GameState::Playing);
+        }
+        GameState::Playing if removed_local_players.iter().next().is_some() => {
+            set_state(&mut state, GameState::Dead);
+        }
+        _ => {}
+    }
+}
+
+fn spawn_state_screen_system(
+    mut commands: Commands,
+    asset_server: Res<AssetServer>,
+    state: Res<State<GameState>>,
+) {
+    let text = match state.current().screen_text() {
+        Some(text) => text,
+        None => return,
+    };
+    commands
+        .spawn_bundle(NodeBundle {
+            style: Style {
+                size: Size::new(Val::Percent(100.0), Val::Percent(100.0)),
+                position_type: PositionType::Absolute,
+                justify_content: JustifyContent::Center,
+                align_items: AlignItems::Center,
+                ..default()
+            },
+            color: Color::rgba(0.0, 0.0, 0.0, 0.6).into(),
+            ..default()
+        })
+        .insert(GameStateScreen)
+        .with_children(|parent| {
+            parent
+                .spawn_bundle(TextBundle::from_section(
+                    text,
+                    TextStyle {
+                        font: asset_server.load(FONT),
+                        font_size: 28.0,
+                        color: Color::PINK,
+                    },
+                ))
+                .insert(GameStateScreenText);
+        });
+}
+
+fn despawn_state_screen_system(
+    mut commands: Commands,
+    query: Query<Entity, With<GameStateScreen>>,
+) {
+    for entity in query.iter() {
+        commands.entity(entity).despawn_recursive();
+    }
+}
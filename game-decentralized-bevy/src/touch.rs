@@ -0,0 +1,182 @@
+//! On-screen touch controls for mobile browsers. The buttons stay hidden until the first touch is
+//! detected and feed the same [`InputActions`] as the keyboard and gamepad.
+use bevy::prelude::*;
+
+use crate::components::{TouchButton, TouchControlsRoot};
+use crate::controls::ControlsLabel;
+use crate::resources::{InputActions, TouchControls};
+use crate::FONT;
+
+const TOUCH_BUTTON_SIZE: f32 = 64.0;
+const TOUCH_BUTTON_MARGIN: f32 = 16.0;
+
+pub struct TouchControlsPlugin;
+
+impl Plugin for TouchControlsPlugin {
+    fn build(
+        &self,
+        app: &mut App,
+    ) {
+        app.init_resource::<TouchControls>();
+        app.add_startup_system(spawn_touch_controls_system);
+        app.add_system_to_stage(
+            CoreStage::PreUpdate,
+            touch_detection_system.before(ControlsLabel::Actions),
+        );
+        app.add_system_to_stage(
+            CoreStage::PreUpdate,
+            touch_actions_system.after(ControlsLabel::Actions),
+        );
+    }
+}
+
+fn spawn_touch_controls_system(
+    mut commands: Commands,
+    asset_server: Res<AssetServer>,
+) {
+    let font = asset_server.load(FONT);
+
+    // (button, label, offset from the left or right edge, anchored on the right)
+    let buttons = [
+        (TouchButton::RotateLeft, "◀", TOUCH_BUTTON_MARGIN, false),
+        (
+            TouchButton::RotateRight,
+            "▶",
+            2.0 * TOUCH_BUTTON_MARGIN + TOUCH_BUTTON_SIZE,
+            false,
+        ),
+        (TouchButton::Fire, "FIRE", TOUCH_BUTTON_MARGIN, true),
+        (
+            TouchButton::Thrust,
+            "▲",
+            2.0 * TOUCH_BUTTON_MARGIN + TOUCH_BUTTON_SIZE,
+            true,
+        ),
+    ];
+
+    commands
+        .spawn_bundle(NodeBundle {
+            style: Style {
+                size: Size::new(Val::Percent(100.0), Val::Percent(100.0)),
+                position_type: PositionType::Absolute,
+                display: Display::None,
+                ..default()
+            },
+            color: Color::NONE.into(),
+            ..default()
+        })
+        .insert(TouchControlsRoot)
+        .with_children(|parent| {
+            for (button, label, offset, anchor_right) in buttons {
+                let position = if anchor_right {
+                    UiRect {
+                        right: Val::Px(offset),
+                        bottom: Val::Px(TOUCH_BUTTON_MARGIN),
+                        ..default()
+                    }
+                } else {
+                    UiRect {
+                        left: Val::Px(offset),
+                        bottom: Val::Px(TOUCH_BUTTON_MARGIN),
+                        ..default()
+                    }
+                };
+                parent
+                    .spawn_bundle(NodeBundle {
+                        style: Style {
+                            size: Size::new(Val::Px(TOUCH_BUTTON_SIZE), Val::Px(TOUCH_BUTTON_SIZE)),
+                            position_type: PositionType::Absolute,
+                            position,
+                            justify_content: JustifyContent::Center,
+                            align_items: AlignItems::Center,
+                            ..default()
+                        },
+                        color: Color::rgba(1.0, 1.0, 1.0, 0.2).into(),
+                        ..default()
+                    })
+                    .insert(button)
+                    .with_children(|parent| {
+                        parent.spawn_bundle(TextBundle::from_section(
+                            label,
+                            TextStyle {
+                                font: font.clone(),
+                                font_size: 18.0,
+                                color: Color::PINK,
+                            },
+                        ));
+                    });
+            }
+        });
+}
+
+fn touch_detection_system(
+    touches: Res<Touches>,
+    mut touch_controls: ResMut<TouchControls>,
+    mut root_query: Query<&mut Style, With<TouchControlsRoot>>,
+) {
+    if touch_controls.detected || touches.iter().next().is_none() {
+        return;
+    }
+
+    // first touch ever: show the on-screen controls
+    touch_controls.detected = true;
+    for mut style in root_query.iter_mut() {
+        style.display = Display::Flex;
+    }
+}
+
+fn touch_actions_system(
+    touches: Res<Touches>,
+    touch_controls: Res<TouchControls>,
+    windows: Res<Windows>,
+    mut input_actions: ResMut<InputActions>,
+    buttons_query: Query<(&TouchButton, &Node, &GlobalTransform)>,
+) {
+    if !touch_controls.detected {
+        return;
+    }
+
+    // the touch positions start from the top of the window, the ui nodes from the bottom
+    let window_height = windows
+        .get_primary()
+        .map(|window| window.height())
+        .unwrap_or_default();
+
+    // find the button under a touch position
+    let button_at = |position: Vec2| {
+        let position = Vec2::new(position.x, window_height - position.y);
+        buttons_query
+            .iter()
+            .find(|(_, node, transform)| {
+                let center = transform.translation().truncate();
+                let extents = node.size / 2.0;
+                let (min, max) = (center - extents, center + extents);
+                (min.x..max.x).contains(&position.x) && (min.y..max.y).contains(&position.y)
+            })
+            .map(|(button, _, _)| *button)
+    };
+
+    // several fingers can be down at once (e.g. rotating while firing)
+    let mut rotation = 0.0;
+    let mut thrust = false;
+    let mut fire_held = false;
+    for touch in touches.iter() {
+        match button_at(touch.position()) {
+            Some(TouchButton::RotateLeft) => rotation += 1.0,
+            Some(TouchButton::RotateRight) => rotation -= 1.0,
+            Some(TouchButton::Thrust) => thrust = true,
+            Some(TouchButton::Fire) => fire_held = true,
+            _ => {}
+        }
+    }
+    let fire = touches
+        .iter_just_pressed()
+        .any(|touch| button_at(touch.position()) == Some(TouchButton::Fire));
+
+    input_actions.rotation = (input_actions.rotation + rotation).clamp(-1.0, 1.0);
+    if thrust {
+        input_actions.thrust = 1.0;
+    }
+    input_actions.fire |= fire;
+    input_actions.fire_held |= fire_held;
+}
@@ -1,13 +1,18 @@
 use crate::components::{
-    Collectible, ExplosionToSpawn, LaserData, Movable, RemoteLaser, SpriteSize, Velocity,
+    Collectible, ExplosionToSpawn, InstructionsText, LaserData, Movable, PlayerTag, RemoteLaser,
+    SpriteSize, TagBar, Velocity,
 };
 use crate::events::PlayerLaserSerializedData;
 use crate::resources::{RemoteCollectibleState, RemoteGamePlayerState};
-use crate::{COLLECTIBLE_SIZE, LASER_LINEAR_MOVEMENT_SPEED, PLAYER_LASER_SIZE, SPRITE_SCALE};
+use crate::{
+    COLLECTIBLE_SIZE, FONT, LASER_LINEAR_MOVEMENT_SPEED, PLAYER_LASER_SIZE, PLAYER_TAG_BAR_SIZE,
+    SPRITE_SCALE,
+};
 use anyhow::{Context, Result};
 use bevy::diagnostic::{FrameTimeDiagnosticsPlugin, LogDiagnosticsPlugin};
 use bevy::math::Vec3Swizzles;
 use bevy::sprite::collide_aabb::collide;
+use bevy::sprite::Anchor;
 use bevy::text::Text2dBounds;
 use bevy::utils::HashMap;
 use bevy::window::PresentMode;
@@ -15,10 +20,8 @@ use bevy::{math::Vec2, prelude::*, time::FixedTimestep};
 use bevy_debug_text_overlay::{screen_print, OverlayPlugin};
 use js_sys::{Array, Function, Map, Object, Reflect, WebAssembly};
 use rand::Rng;
-use std::collections::HashSet;
+use std::collections::{BTreeMap, BTreeSet, HashSet};
 use std::hash::Hash;
-use std::str::FromStr;
-use uuid::Uuid;
 use wasm_bindgen::{JsCast, JsValue};
 
 pub fn get_random_f32(
@@ -35,8 +38,9 @@ pub fn get_random_uuid() -> uuid::Uuid {
 pub fn spawn_game_screen_instructions(
     commands: &mut Commands,
     asset_server: &Res<AssetServer>,
+    instructions: &str,
 ) -> Entity {
-    let font = asset_server.load("entities/FiraMono-Medium.ttf");
+    let font = asset_server.load(FONT);
     let text_style = TextStyle {
         font,
         font_size: 15.0,
@@ -47,105 +51,169 @@ pub fn spawn_game_screen_instructions(
     let box_position = Vec2::new(280.0, 250.0);
     let text_2d_entity = commands
         .spawn_bundle(Text2dBundle {
-            text: Text::from_section("← → left/right, SPACE - shoot", text_style),
+            text: Text::from_section(instructions, text_style),
             text_2d_bounds: Text2dBounds { size: box_size },
             transform: Transform::from_xyz(box_position.x, box_position.y, 2.0),
             ..default()
         })
+        .insert(InstructionsText)
         .id();
     text_2d_entity
 }
 
+/// Spawns the name tag of a player, made of its name above a health/shield bar, as a child of
+/// the player ship
 pub fn spawn_player_name_text2d_entity(
     commands: &mut Commands,
     asset_server: &Res<AssetServer>,
+    player_entity: Entity,
     player_name: &str,
-    player_position: &Vec3,
+    color: Color,
 ) -> Entity {
-    let font = asset_server.load("entities/FiraMono-Medium.ttf");
+    let font = asset_server.load(FONT);
     let text_style = TextStyle {
         font,
         font_size: 15.0,
-        color: Color::GREEN,
+        color,
     };
     let text_alignment = TextAlignment::CENTER;
     let box_size = Vec2::new(200.0, 100.0);
-    let box_position = Vec2::new(player_position.x, player_position.y);
-    let text_2d_entity = commands
-        .spawn_bundle(Text2dBundle {
-            text: Text::from_section(player_name, text_style),
-            text_2d_bounds: Text2dBounds { size: box_size },
-            transform: Transform::from_xyz(
-                box_position.x - box_size.x / 2.0 + 35.0,
-                box_position.y + box_size.y / 2.0,
-                2.0,
-            ),
-            ..default()
+    let (bar_width, bar_height) = PLAYER_TAG_BAR_SIZE;
+    let bar_position = Vec2::new(-bar_width / 2.0, -14.0);
+    let tag_entity = commands
+        .spawn_bundle(SpatialBundle::default())
+        .insert(PlayerTag)
+        .with_children(|tag| {
+            tag.spawn_bundle(Text2dBundle {
+                text: Text::from_section(player_name, text_style).with_alignment(text_alignment),
+                text_2d_bounds: Text2dBounds { size: box_size },
+                ..default()
+            });
+            tag.spawn_bundle(SpriteBundle {
+                sprite: Sprite {
+                    color: Color::rgba(0.0, 0.0, 0.0, 0.6),
+                    custom_size: Some(Vec2::new(bar_width + 2.0, bar_height + 2.0)),
+                    anchor: Anchor::CenterLeft,
+                    ..default()
+                },
+                transform: Transform::from_xyz(bar_position.x - 1.0, bar_position.y, 0.0),
+                ..default()
+            })
+            .insert(TagBar::Background);
+            tag.spawn_bundle(SpriteBundle {
+                sprite: Sprite {
+                    color: Color::GREEN,
+                    custom_size: Some(Vec2::new(bar_width, bar_height)),
+                    anchor: Anchor::CenterLeft,
+                    ..default()
+                },
+                transform: Transform::from_xyz(bar_position.x, bar_position.y, 0.1),
+                ..default()
+            })
+            .insert(TagBar::Health);
+            tag.spawn_bundle(SpriteBundle {
+                sprite: Sprite {
+                    color: Color::CYAN,
+                    custom_size: Some(Vec2::new(0.0, bar_height / 2.0)),
+                    anchor: Anchor::CenterLeft,
+                    ..default()
+                },
+                transform: Transform::from_xyz(bar_position.x, bar_position.y, 0.2),
+                ..default()
+            })
+            .insert(TagBar::Shield);
         })
         .id();
-    text_2d_entity
+    commands.entity(player_entity).add_child(tag_entity);
+    tag_entity
+}
+
+/// Differences between the current entries, by key, and a full latest state
+#[derive(Debug)]
+pub struct KeyedDiff<K, V> {
+    /// values of the latest state without a current entry
+    pub added: Vec<V>,
+    /// values of the latest state whose current entry differs
+    pub changed: Vec<V>,
+    /// keys of the current entries missing from the latest state
+    pub removed: Vec<K>,
 }
 
-/// Extracts the common values in `a` and `b` into a new set.
-pub fn inplace_intersection<T>(
-    a: &mut HashSet<T>,
-    b: &mut HashSet<T>,
-) -> HashSet<T>
+/// Diffs the current entries against a full latest state: the keys only in the latest state are
+/// added, the ones only in the current entries removed, and the common ones split by
+/// `has_changed`. A key repeated in the latest state only counts once (the first value).
+pub fn keyed_diff<K, C, V>(
+    current: &BTreeMap<K, C>,
+    latest: Vec<V>,
+    key: impl Fn(&V) -> K,
+    has_changed: impl Fn(&C, &V) -> bool,
+) -> KeyedDiff<K, V>
 where
-    T: Hash,
-    T: Eq,
+    K: Ord + Clone,
 {
-    let x: HashSet<(T, bool)> = a
-        .drain()
-        .map(|v| {
-            let intersects = b.contains(&v);
-            (v, intersects)
-        })
-        .collect();
-
-    let mut c = HashSet::new();
-    for (v, is_inter) in x {
-        if is_inter {
-            c.insert(v);
-        } else {
-            a.insert(v);
+    let mut diff = KeyedDiff {
+        added: vec![],
+        changed: vec![],
+        removed: vec![],
+    };
+    let mut latest_keys = BTreeSet::new();
+    for value in latest.into_iter() {
+        let value_key = key(&value);
+        if latest_keys.contains(&value_key) {
+            continue;
+        }
+        match current.get(&value_key) {
+            Some(entry) => {
+                if has_changed(entry, &value) {
+                    diff.changed.push(value);
+                }
+            }
+            None => diff.added.push(value),
         }
+        latest_keys.insert(value_key);
     }
-
-    b.retain(|v| !c.contains(&v));
-
-    c
+    diff.removed = current
+        .keys()
+        .filter(|current_key| !latest_keys.contains(*current_key))
+        .cloned()
+        .collect();
+    diff
 }
 
+/// Spawns a remote laser, shot `state.age` seconds before the game time `now`. Nothing is spawned
+/// for a laser without a valid uuid.
 pub fn spawn_laser_closure(
     commands: &mut Commands,
     laser_texture: Handle<Image>,
     state: PlayerLaserSerializedData,
-) -> Entity {
-    commands
+    now: f64,
+) -> Option<Entity> {
+    let laser = LaserData::from_remote(&state, now)?;
+    let entity = commands
         .spawn_bundle(SpriteBundle {
             texture: laser_texture,
             transform: Transform {
                 translation: Vec3::new(state.x as f32, state.y as f32, 1.0), // set z axis to 1 so tokens stay above
                 rotation: Quat::from_array([0., 0., state.rot as f32, state.w as f32]),
-                scale: Vec3::new(SPRITE_SCALE, SPRITE_SCALE, 1.),
+                scale: Vec3::new(
+                    state.weapon.projectile_scale(),
+                    state.weapon.projectile_scale(),
+                    1.,
+                ),
                 ..Default::default()
             },
             ..Default::default()
         })
-        .insert(RemoteLaser(LaserData {
-            uuid: Uuid::from_str(&state.uuid).expect("A proper uuid"),
-            player_uuid: state.player_uuid.clone(),
-            start_pos: Vec3::new(state.x as f32, state.y as f32, 1.0),
-            start_rot: Quat::from_array([0., 0., state.rot as f32, state.w as f32]),
-        }))
+        .insert(RemoteLaser(laser))
         .insert(SpriteSize::from(PLAYER_LASER_SIZE))
         .insert(Movable { auto_despawn: true })
         .insert(Velocity {
-            linear: LASER_LINEAR_MOVEMENT_SPEED,
+            linear: state.weapon.projectile_speed(),
             rotational: f32::to_radians(0.0),
+            ..Default::default()
         })
-        .id()
+        .id();
+    Some(entity)
 }
 
 pub fn spawn_collectible_closure(
@@ -156,6 +224,10 @@ pub fn spawn_collectible_closure(
     commands
         .spawn_bundle(SpriteBundle {
             texture: collectible_texture,
+            sprite: Sprite {
+                color: state.kind.tint(),
+                ..Default::default()
+            },
             transform: Transform {
                 translation: Vec3::new(state.position.x, state.position.y, 1.0), // set z axis to 1 so tokens stay above
                 scale: Vec3::new(0.5, 0.5, 1.),
@@ -167,3 +239,51 @@ pub fn spawn_collectible_closure(
         .insert(SpriteSize::from(COLLECTIBLE_SIZE))
         .id()
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn current() -> BTreeMap<u32, i32> {
+        BTreeMap::from([(1, 10), (2, 20), (3, 30)])
+    }
+
+    #[test]
+    fn keyed_diff_splits_added_changed_and_removed() {
+        let diff = keyed_diff(
+            &current(),
+            vec![(2, 21), (3, 30), (4, 40)],
+            |(key, _)| *key,
+            |current, (_, latest)| current != latest,
+        );
+        assert_eq!(diff.added, vec![(4, 40)]);
+        assert_eq!(diff.changed, vec![(2, 21)]);
+        assert_eq!(diff.removed, vec![1]);
+    }
+
+    #[test]
+    fn keyed_diff_keeps_the_first_repeated_key() {
+        let diff = keyed_diff(
+            &current(),
+            vec![(1, 10), (1, 11), (5, 50), (5, 51)],
+            |(key, _)| *key,
+            |current, (_, latest)| current != latest,
+        );
+        assert_eq!(diff.added, vec![(5, 50)]);
+        assert!(diff.changed.is_empty());
+        assert_eq!(diff.removed, vec![2, 3]);
+    }
+
+    #[test]
+    fn keyed_diff_against_an_empty_state_removes_everything() {
+        let diff = keyed_diff(
+            &current(),
+            vec![],
+            |(key, _): &(u32, i32)| *key,
+            |current, (_, latest)| current != latest,
+        );
+        assert!(diff.added.is_empty());
+        assert!(diff.changed.is_empty());
+        assert_eq!(diff.removed, vec![1, 2, 3]);
+    }
+}
@@ -1,7 +1,12 @@
-use bevy::prelude::{Quat, Vec3};
+use bevy::prelude::{Quat, Vec2, Vec3};
 use std::cell::RefCell;
 use wasm_bindgen::prelude::*;
 
+use crate::inbox::UpdateInbox;
+use crate::skins::skin_names;
+use crate::snapshot::{GameSnapshot, SNAPSHOT_VERSION};
+use crate::INBOUND_QUEUE_CAPACITY;
+
 #[wasm_bindgen]
 extern "C" {
     #[wasm_bindgen(js_namespace = console)]
@@ -9,7 +14,7 @@ extern "C" {
 }
 
 // Local single thread recording player movements on every frame RUST -> JS
-thread_local!(pub static LOCAL_PLAYER_POSITION: RefCell<Position> = RefCell::new(Position{x: 0.0, y: 0.0, rot: 0.0, w: 0.0}));
+thread_local!(pub static LOCAL_PLAYER_POSITION: RefCell<Position> = RefCell::new(Position{x: 0.0, y: 0.0, rot: 0.0, w: 0.0, vx: 0.0, vy: 0.0}));
 
 #[derive(Debug, Clone, Copy)]
 pub struct Position {
@@ -17,6 +22,8 @@ pub struct Position {
     pub y: f32,
     pub rot: f32,
     pub w: f32,
+    pub vx: f32,
+    pub vy: f32,
 }
 
 impl Position {
@@ -24,12 +31,15 @@ impl Position {
         &mut self,
         pos: Vec3,
         rot: Quat,
+        vel: Vec2,
     ) {
         //let (vec, angle) = rot.to_axis_angle();
         self.x = pos.x;
         self.y = pos.y;
         self.rot = rot.z;
         self.w = rot.w;
+        self.vx = vel.x;
+        self.vy = vel.y;
     }
 
     pub fn set_pos(
@@ -73,14 +83,31 @@ pub fn get_player_w() -> f32 {
     rotation
 }
 
+// a method callable from js to get player x velocity
+#[wasm_bindgen]
+pub fn get_player_vx() -> f32 {
+    let velocity = LOCAL_PLAYER_POSITION.with(|pos| pos.borrow().vx);
+    velocity
+}
+
+// a method callable from js to get player y velocity
+#[wasm_bindgen]
+pub fn get_player_vy() -> f32 {
+    let velocity = LOCAL_PLAYER_POSITION.with(|pos| pos.borrow().vy);
+    velocity
+}
+
 // ========================================================================================== //
 
 // Local single thread recording player lasers on every frame RUST -> JS
 thread_local!(pub static LOCAL_PLAYER_LASERS: RefCell<Option<String>> = RefCell::new(None));
 
-// a method callable from js to get player x lasers state (serialized)
+// a method callable from js to get player x lasers state (serialized), none while spectating
 #[wasm_bindgen]
 pub fn get_player_lasers() -> Option<String> {
+    if is_spectating() {
+        return None;
+    }
     LOCAL_PLAYER_LASERS.with(|pos| {
         let x = &*pos.borrow();
         x.clone()
@@ -89,6 +116,242 @@ pub fn get_player_lasers() -> Option<String> {
 
 // ========================================================================================== //
 
+// Local single thread recording the input bindings (serialized) RUST -> JS
+thread_local!(pub static INPUT_BINDINGS: RefCell<Option<String>> = RefCell::new(None));
+
+// a method callable from js to get the input bindings as a json object of action name to key name
+#[wasm_bindgen]
+pub fn get_input_bindings() -> Option<String> {
+    INPUT_BINDINGS.with(|bindings| bindings.borrow().clone())
+}
+
+// local communication thread for rebinding requests [JS (write) --> RUST game loop (read)]
+thread_local!(pub static INPUT_REBIND_REQUESTS: RefCell<Vec<(String, String)>> = RefCell::new(vec![]));
+
+// JS call to bind a key to an action, e.g. rebind_input_action("Fire", "F")
+#[wasm_bindgen]
+pub fn rebind_input_action(
+    action: String,
+    key: String,
+) {
+    INPUT_REBIND_REQUESTS.with(|requests| requests.borrow_mut().push((action, key)));
+}
+
+// ========================================================================================== //
+
+// Local single thread recording the current game state RUST -> JS
+thread_local!(pub static GAME_PHASE: RefCell<String> = RefCell::new(String::from("Loading")));
+
+// a method callable from js to get the current game state (e.g. "Playing")
+#[wasm_bindgen]
+pub fn get_game_phase() -> String {
+    GAME_PHASE.with(|phase| phase.borrow().clone())
+}
+
+// local communication thread for game state changes [JS (write) --> RUST game loop (read)]
+thread_local!(pub static GAME_PHASE_REQUESTS: RefCell<Vec<String>> = RefCell::new(vec![]));
+
+// JS call to switch the game state: "Loading", "WaitingForPlayer", "Playing", "Dead",
+// "Disconnected" or "Spectating". Applied once the game assets are loaded.
+#[wasm_bindgen]
+pub fn set_game_phase(phase: String) {
+    GAME_PHASE_REQUESTS.with(|requests| requests.borrow_mut().push(phase));
+}
+
+// Local single thread recording whether the game is paused because the page is hidden RUST -> JS
+thread_local!(pub static GAME_PAUSED: RefCell<bool> = RefCell::new(false));
+
+// a method callable from js to know whether to hold the outbound player state (hidden page)
+#[wasm_bindgen]
+pub fn is_game_paused() -> bool {
+    GAME_PAUSED.with(|paused| *paused.borrow())
+}
+
+// Local single thread recording whether the game is in spectator mode RUST -> JS
+thread_local!(pub static SPECTATING: RefCell<bool> = RefCell::new(false));
+
+// a method callable from js to know whether to hold the outbound player state (spectator mode, no
+// local player: the position getters return zeros and the lasers getter none)
+#[wasm_bindgen]
+pub fn is_spectating() -> bool {
+    SPECTATING.with(|spectating| *spectating.borrow())
+}
+
+// Local single thread recording whether all the game assets are loaded RUST -> JS
+thread_local!(pub static GAME_READY: RefCell<bool> = RefCell::new(false));
+
+// callback registered by js, called once the game is ready
+thread_local!(pub static GAME_READY_CALLBACK: RefCell<Option<js_sys::Function>> = RefCell::new(None));
+
+// Local single thread recording the assets which failed to load RUST -> JS
+thread_local!(pub static GAME_ASSET_ERRORS: RefCell<Vec<String>> = RefCell::new(vec![]));
+
+// a method callable from js to know whether the game assets are loaded
+#[wasm_bindgen]
+pub fn is_game_ready() -> bool {
+    GAME_READY.with(|ready| *ready.borrow())
+}
+
+// JS call to register a callback called once the game is ready (right away if it already is)
+#[wasm_bindgen]
+pub fn on_game_ready(callback: js_sys::Function) {
+    if is_game_ready() {
+        let _ = callback.call0(&JsValue::NULL);
+    } else {
+        GAME_READY_CALLBACK.with(|ready_callback| *ready_callback.borrow_mut() = Some(callback));
+    }
+}
+
+// a method callable from js to get the paths of the assets which failed to load, as a json array
+#[wasm_bindgen]
+pub fn get_asset_load_errors() -> String {
+    GAME_ASSET_ERRORS.with(|errors| serde_json::to_string(&*errors.borrow()).unwrap_or_default())
+}
+
+// marks the game as ready and notifies js
+pub fn set_game_ready() {
+    GAME_READY.with(|ready| *ready.borrow_mut() = true);
+    if let Some(callback) = GAME_READY_CALLBACK.with(|ready_callback| ready_callback.take()) {
+        let _ = callback.call0(&JsValue::NULL);
+    }
+}
+
+// ========================================================================================== //
+
+// a method callable from js to list the skins players can pick, as a json array of names
+#[wasm_bindgen]
+pub fn get_player_skins() -> String {
+    serde_json::to_string(&skin_names()).unwrap_or_default()
+}
+
+// ========================================================================================== //
+
+// local communication thread for audio volume changes [JS (write) --> RUST game loop (read)]
+thread_local!(pub static AUDIO_VOLUME_REQUESTS: RefCell<Vec<(String, f32)>> = RefCell::new(vec![]));
+
+// JS call to set the master volume, in [0, 1]
+#[wasm_bindgen]
+pub fn set_master_volume(volume: f32) {
+    AUDIO_VOLUME_REQUESTS
+        .with(|requests| requests.borrow_mut().push(("master".to_string(), volume)));
+}
+
+// JS call to set the sound effects volume, in [0, 1]
+#[wasm_bindgen]
+pub fn set_sfx_volume(volume: f32) {
+    AUDIO_VOLUME_REQUESTS.with(|requests| requests.borrow_mut().push(("sfx".to_string(), volume)));
+}
+
+// JS call to set the music volume, in [0, 1]
+#[wasm_bindgen]
+pub fn set_music_volume(volume: f32) {
+    AUDIO_VOLUME_REQUESTS
+        .with(|requests| requests.borrow_mut().push(("music".to_string(), volume)));
+}
+
+// set from js once a user gesture happened outside the game canvas (e.g. a click on a menu)
+thread_local!(pub static AUDIO_UNLOCK_REQUESTED: RefCell<bool> = RefCell::new(false));
+
+// JS call to let the game start its audio, to be made from a user gesture handler
+#[wasm_bindgen]
+pub fn unlock_audio() {
+    AUDIO_UNLOCK_REQUESTED.with(|requested| *requested.borrow_mut() = true);
+}
+
+// ========================================================================================== //
+
+// Local single thread recording the network metrics as json RUST -> JS
+thread_local!(pub static NETWORK_METRICS: RefCell<String> = RefCell::new(String::from("{}")));
+
+// a method callable from js to get the network metrics object (updates per second by operation,
+// queue depth, dropped, coalesced and discarded updates, decode errors, unknown operations, stale
+// updates, seconds since the last update per remote player, chain latency, fps and entity counts),
+// refreshed every second
+#[wasm_bindgen]
+pub fn get_network_metrics() -> JsValue {
+    NETWORK_METRICS.with(|metrics| js_sys::JSON::parse(&metrics.borrow()).unwrap_or(JsValue::NULL))
+}
+
+// local communication thread for the diagnostics overlay visibility [JS (write) --> RUST game loop (read)]
+thread_local!(pub static DIAGNOSTICS_VISIBILITY_REQUEST: RefCell<Option<bool>> = RefCell::new(None));
+
+// JS call to show or hide the diagnostics overlay
+#[wasm_bindgen]
+pub fn set_diagnostics_visible(visible: bool) {
+    DIAGNOSTICS_VISIBILITY_REQUEST.with(|request| *request.borrow_mut() = Some(visible));
+}
+
+// ========================================================================================== //
+
+// local communication thread for the deterministic simulation toggle [JS (write) --> RUST game loop (read)]
+thread_local!(pub static DETERMINISTIC_SIMULATION_REQUEST: RefCell<Option<bool>> = RefCell::new(None));
+
+// JS call to simulate the local ship and the lasers with the deterministic fixed-point core (the
+// same inputs give bit-identical positions on every client), off by default
+#[wasm_bindgen]
+pub fn set_deterministic_simulation(enabled: bool) {
+    DETERMINISTIC_SIMULATION_REQUEST.with(|request| *request.borrow_mut() = Some(enabled));
+}
+
+// ========================================================================================== //
+
+// Local single thread recording the movement plausibility report as json RUST -> JS
+thread_local!(pub static MOVEMENT_REPORT: RefCell<String> = RefCell::new(String::from("{}")));
+
+// a method callable from js to get the movement plausibility report (policy, and per remote player
+// the checked and implausible moves, the last failed checks and whether it is flagged), refreshed
+// every second
+#[wasm_bindgen]
+pub fn get_movement_report() -> JsValue {
+    MOVEMENT_REPORT.with(|report| js_sys::JSON::parse(&report.borrow()).unwrap_or(JsValue::NULL))
+}
+
+// local communication thread for movement policy changes [JS (write) --> RUST game loop (read)]
+thread_local!(pub static MOVEMENT_POLICY_REQUESTS: RefCell<Vec<String>> = RefCell::new(vec![]));
+
+// JS call to choose what to do with the implausible remote moves: "clamp" or "ignore"
+#[wasm_bindgen]
+pub fn set_movement_policy(policy: String) {
+    MOVEMENT_POLICY_REQUESTS.with(|requests| requests.borrow_mut().push(policy));
+}
+
+// ========================================================================================== //
+
+// Local single thread recording the latest game snapshot as json RUST -> JS
+thread_local!(pub static GAME_SNAPSHOT: RefCell<String> = RefCell::new(String::from("{}")));
+
+// a method callable from js to get the game snapshot (players, collectibles, lasers and scores, with
+// their chain and live states), refreshed every second
+#[wasm_bindgen]
+pub fn export_game_snapshot() -> String {
+    GAME_SNAPSHOT.with(|snapshot| snapshot.borrow().clone())
+}
+
+// local communication thread for snapshot imports [JS (write) --> RUST game loop (read)]
+thread_local!(pub static GAME_SNAPSHOT_IMPORTS: RefCell<Vec<GameSnapshot>> = RefCell::new(vec![]));
+
+// JS call to replace the game world by a snapshot (e.g. provided by a server to a late-joining
+// client), returns false for an invalid snapshot
+#[wasm_bindgen]
+pub fn import_game_snapshot(json: String) -> bool {
+    match serde_json::from_str::<GameSnapshot>(&json) {
+        Ok(snapshot) if snapshot.version == SNAPSHOT_VERSION => {
+            GAME_SNAPSHOT_IMPORTS.with(|imports| imports.borrow_mut().push(snapshot));
+            true
+        }
+        Ok(snapshot) => {
+            log(format!("Unsupported game snapshot version {:?}", snapshot.version).as_str());
+            false
+        }
+        Err(err) => {
+            log(format!("Invalid game snapshot: {:?}", err.to_string()).as_str());
+            false
+        }
+    }
+}
+
+// ========================================================================================== //
+
 // A JS < -- > RUST mapped object
 #[wasm_bindgen(module = "src/app/GameEntity.ts")]
 extern "C" {
@@ -96,7 +359,7 @@ extern "C" {
     pub type GameEntityUpdate;
 
     #[wasm_bindgen(constructor)]
-    fn new_player(
+    pub fn new_player(
         operation: String,
         data: String,
     ) -> GameEntityUpdate;
@@ -123,21 +386,17 @@ extern "C" {
 }
 
 // local communication thread between js and the game engine [JS (write) --> RUST game loop (read)]
-thread_local!(pub static GAME_ENTITY_UPDATE: RefCell<Vec<GameEntityUpdate>> = RefCell::new(vec![]));
+thread_local!(pub static GAME_ENTITY_UPDATE: RefCell<UpdateInbox> = RefCell::new(UpdateInbox::new(INBOUND_QUEUE_CAPACITY)));
 
 // JS call to set a vec of updates which the game engine (rust) needs to process
 #[wasm_bindgen]
 pub fn push_game_entity_updates(updated_entities: Vec<GameEntityUpdate>) {
-    GAME_ENTITY_UPDATE.with(|pos| {
-        //let entities_updated = updated_entities.len();
-        pos.borrow_mut().extend(updated_entities.into_iter());
-        /*
-        log(format!(
-            "Entities updated {:?}. Queue size: {:?}",
-            entities_updated,
-            pos.borrow().len()
-        )
-        .as_str());
-        */
+    // the queue depth and the dropped updates are reported by the diagnostics (see
+    // `get_network_metrics`)
+    GAME_ENTITY_UPDATE.with(|inbox| {
+        let mut inbox = inbox.borrow_mut();
+        for update in updated_entities.into_iter() {
+            inbox.push(update);
+        }
     });
 }
@@ -0,0 +1,248 @@
+//! Ship weapons: every kind has its own projectile stats, and a [`Weapon`] limits the fire rate
+//! with a cooldown and overheats when fired too often.
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::{LASER_LINEAR_MOVEMENT_SPEED, LASER_MAX_RANGE, SPRITE_SCALE};
+
+// heat lost per second
+const WEAPON_COOLING_RATE: f32 = 0.35;
+// an overheated weapon fires again once its heat drops below this level
+const WEAPON_OVERHEAT_RECOVERY: f32 = 0.3;
+// seconds a charged beam must be held before it can be released
+const WEAPON_BEAM_CHARGE_TIME: f32 = 0.8;
+
+/// The weapon a laser was shot with. Sent along with every laser so that remote clients render it
+/// with the right speed and size.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum WeaponKind {
+    SingleLaser,
+    SpreadShot,
+    ChargedBeam,
+}
+
+impl Default for WeaponKind {
+    fn default() -> Self {
+        WeaponKind::SingleLaser
+    }
+}
+
+impl WeaponKind {
+    /// projectile speed, in meters per second
+    pub fn projectile_speed(&self) -> f32 {
+        match self {
+            WeaponKind::SingleLaser => LASER_LINEAR_MOVEMENT_SPEED,
+            WeaponKind::SpreadShot => LASER_LINEAR_MOVEMENT_SPEED * 0.8,
+            WeaponKind::ChargedBeam => LASER_LINEAR_MOVEMENT_SPEED * 1.5,
+        }
+    }
+
+    /// max distance travelled by a projectile from where it was shot, in units
+    pub fn projectile_range(&self) -> f32 {
+        match self {
+            WeaponKind::SingleLaser => LASER_MAX_RANGE,
+            WeaponKind::SpreadShot => LASER_MAX_RANGE * 0.6,
+            WeaponKind::ChargedBeam => LASER_MAX_RANGE * 1.5,
+        }
+    }
+
+    /// projectile sprite scale
+    pub fn projectile_scale(&self) -> f32 {
+        match self {
+            WeaponKind::SingleLaser => SPRITE_SCALE,
+            WeaponKind::SpreadShot => SPRITE_SCALE * 0.75,
+            WeaponKind::ChargedBeam => SPRITE_SCALE * 2.0,
+        }
+    }
+
+    /// damage dealt by one projectile
+    pub fn damage(&self) -> f32 {
+        match self {
+            WeaponKind::SingleLaser => 25.0,
+            WeaponKind::SpreadShot => 15.0,
+            WeaponKind::ChargedBeam => 80.0,
+        }
+    }
+
+    /// minimum seconds between two shots
+    pub fn cooldown(&self) -> f32 {
+        match self {
+            WeaponKind::SingleLaser => 0.25,
+            WeaponKind::SpreadShot => 0.6,
+            WeaponKind::ChargedBeam => 1.0,
+        }
+    }
+
+    /// heat added by one shot, the weapon overheats at 1.0
+    pub fn heat_per_shot(&self) -> f32 {
+        match self {
+            WeaponKind::SingleLaser => 0.12,
+            WeaponKind::SpreadShot => 0.3,
+            WeaponKind::ChargedBeam => 0.5,
+        }
+    }
+
+    /// angles (in radians, relative to the ship heading) of the projectiles of one shot
+    pub fn spread_angles(&self) -> &'static [f32] {
+        match self {
+            WeaponKind::SingleLaser | WeaponKind::ChargedBeam => &[0.0],
+            WeaponKind::SpreadShot => &[-0.25, 0.0, 0.25],
+        }
+    }
+
+    pub fn next(&self) -> WeaponKind {
+        match self {
+            WeaponKind::SingleLaser => WeaponKind::SpreadShot,
+            WeaponKind::SpreadShot => WeaponKind::ChargedBeam,
+            WeaponKind::ChargedBeam => WeaponKind::SingleLaser,
+        }
+    }
+}
+
+#[derive(Component, Clone, Debug, Default)]
+pub struct Weapon {
+    pub kind: WeaponKind,
+    /// seconds left before the next shot is allowed
+    pub cooldown: f32,
+    /// heat level, in [0, 1]
+    pub heat: f32,
+    pub overheated: bool,
+    /// seconds the charged beam has been held for
+    pub charge: f32,
+}
+
+impl Weapon {
+    /// Cools the weapon down by `dt` seconds
+    pub fn tick(
+        &mut self,
+        dt: f32,
+    ) {
+        self.cooldown = (self.cooldown - dt).max(0.0);
+        self.heat = (self.heat - WEAPON_COOLING_RATE * dt).max(0.0);
+        if self.overheated && self.heat <= WEAPON_OVERHEAT_RECOVERY {
+            self.overheated = false;
+        }
+    }
+
+    pub fn can_fire(&self) -> bool {
+        self.cooldown <= 0.0 && !self.overheated
+    }
+
+    /// Decides whether the weapon fires this step, given the latched fire request and whether the
+    /// fire input is held down (charged beams fire when released after a full charge)
+    pub fn trigger(
+        &mut self,
+        fire: bool,
+        fire_held: bool,
+        dt: f32,
+    ) -> bool {
+        let shoot = match self.kind {
+            WeaponKind::ChargedBeam => {
+                if fire_held {
+                    self.charge += dt;
+                    false
+                } else {
+                    let charged = self.charge >= WEAPON_BEAM_CHARGE_TIME;
+                    self.charge = 0.0;
+                    charged
+                }
+            }
+            _ => fire,
+        };
+        if !shoot || !self.can_fire() {
+            return false;
+        }
+
+        self.cooldown = self.kind.cooldown();
+        self.heat += self.kind.heat_per_shot();
+        if self.heat >= 1.0 {
+            self.heat = 1.0;
+            self.overheated = true;
+        }
+        true
+    }
+
+    pub fn switch(&mut self) {
+        self.kind = self.kind.next();
+        self.charge = 0.0;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn weapon(kind: WeaponKind) -> Weapon {
+        Weapon {
+            kind,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn the_cooldown_blocks_a_second_shot() {
+        let mut weapon = weapon(WeaponKind::SingleLaser);
+        assert!(weapon.trigger(true, true, 0.0));
+        assert!(!weapon.trigger(true, true, 0.0));
+
+        weapon.tick(WeaponKind::SingleLaser.cooldown() / 2.0);
+        assert!(!weapon.trigger(true, true, 0.0));
+        weapon.tick(WeaponKind::SingleLaser.cooldown() / 2.0);
+        assert!(weapon.trigger(true, true, 0.0));
+    }
+
+    #[test]
+    fn an_overheated_weapon_fires_again_once_cooled_down() {
+        let mut weapon = Weapon {
+            heat: 0.95,
+            ..weapon(WeaponKind::SingleLaser)
+        };
+        assert!(weapon.trigger(true, false, 0.0));
+        assert!(weapon.overheated);
+        assert_eq!(weapon.heat, 1.0);
+
+        // locked out past the cooldown, until the heat drops to the recovery level
+        weapon.tick(1.9);
+        assert!(weapon.overheated);
+        assert!(!weapon.trigger(true, false, 0.0));
+        weapon.tick(0.2);
+        assert!(!weapon.overheated);
+        assert!(weapon.trigger(true, false, 0.0));
+    }
+
+    #[test]
+    fn a_charged_beam_fires_when_released_after_a_full_charge() {
+        let mut weapon = weapon(WeaponKind::ChargedBeam);
+        // the fire requests are ignored, only the release counts
+        assert!(!weapon.trigger(true, true, 0.5));
+        assert!(!weapon.trigger(false, false, 0.0));
+        assert_eq!(weapon.charge, 0.0);
+
+        assert!(!weapon.trigger(false, true, 0.5));
+        assert!(!weapon.trigger(false, true, 0.5));
+        assert!(weapon.trigger(false, false, 0.0));
+        assert_eq!(weapon.charge, 0.0);
+        assert_eq!(weapon.heat, WeaponKind::ChargedBeam.heat_per_shot());
+    }
+
+    #[test]
+    fn the_other_weapons_fire_on_request_without_charging() {
+        for kind in [WeaponKind::SingleLaser, WeaponKind::SpreadShot] {
+            let mut weapon = weapon(kind);
+            assert!(!weapon.trigger(false, true, 1.0));
+            assert_eq!(weapon.charge, 0.0);
+            assert!(weapon.trigger(true, false, 0.0));
+            assert_eq!(weapon.cooldown, kind.cooldown());
+        }
+    }
+
+    #[test]
+    fn switching_weapons_drops_the_charge() {
+        let mut weapon = weapon(WeaponKind::ChargedBeam);
+        assert!(!weapon.trigger(false, true, 1.0));
+        weapon.switch();
+        assert_eq!(weapon.kind, WeaponKind::SingleLaser);
+        assert_eq!(weapon.charge, 0.0);
+    }
+}